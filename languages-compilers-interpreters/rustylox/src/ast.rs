@@ -1,11 +1,25 @@
 use super::token::Token;
 use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-#[derive(Debug)]
+/// Identifies a variable reference so the resolver can record its scope depth
+/// without mutating the tree. Ids are unique for the life of the process, so
+/// functions defined by earlier REPL inputs never collide with later ones.
+pub type ExprId = usize;
+
+pub fn next_expr_id() -> ExprId {
+    static NEXT_EXPR_ID: AtomicUsize = AtomicUsize::new(0);
+    NEXT_EXPR_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
 pub enum Stmt {
     Break,
+    Class(Token, Option<Expr>, Vec<Stmt>),
     ExprStmt(Expr),
+    Function(Token, Vec<Token>, Vec<Stmt>),
     Print(Expr),
+    Return(Token, Option<Expr>),
     VarDeclaration(Token, Option<Expr>),
     Block(Vec<Stmt>),
     IfStmt(Expr, Box<Stmt>, Option<Box<Stmt>>),
@@ -14,65 +28,335 @@ pub enum Stmt {
 
 #[derive(Debug, Clone)]
 pub enum Expr {
-    Assignment(Token, Box<Expr>),
+    Assignment(ExprId, Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
     Call(Box<Expr>, Token, Vec<Expr>),
+    Get(Box<Expr>, Token),
     Grouping(Box<Expr>),
-    Identifier(String),
-    TrueLiteral,
-    FalseLiteral,
-    NilLiteral,
-    NumberLiteral(f64),
-    StringLiteral(String),
+    TrueLiteral(Token),
+    FalseLiteral(Token),
+    NilLiteral(Token),
+    NumberLiteral(Token, f64),
+    StringLiteral(Token, String),
+    Set(Box<Expr>, Token, Box<Expr>),
+    Super(ExprId, Token, Token),
+    This(ExprId, Token),
     Unary(Token, Box<Expr>),
-    Variable(Token),
+    Variable(ExprId, Token),
     Logical(Box<Expr>, Token, Box<Expr>),
 }
 
+// Both enums display as S-expressions, e.g. `-123 * (45.67)` prints as
+// `(* (- 123) (group 45.67))`. The output leaves out expression ids and token
+// positions so that it stays stable across runs and is easy to compare in
+// tests.
 impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let _ = write!(f, "{}", "(");
-        match &*self {
-            Expr::Assignment(name, value) => {
-                let _ = write!(f, "{} = {}", name, value);
+        match self {
+            Expr::Assignment(_, name, value) => write!(f, "(= {} {})", name.lexeme, value),
+            Expr::Binary(left, op, right) | Expr::Logical(left, op, right) => {
+                write!(f, "({} {} {})", op.lexeme, left, right)
+            }
+            Expr::Call(callee, _, args) => {
+                write!(f, "(call {}", callee)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Get(object, name) => write!(f, "(. {} {})", object, name.lexeme),
+            Expr::Grouping(expr) => write!(f, "(group {})", expr),
+            Expr::TrueLiteral(_) => write!(f, "true"),
+            Expr::FalseLiteral(_) => write!(f, "false"),
+            Expr::NilLiteral(_) => write!(f, "nil"),
+            Expr::NumberLiteral(_, n) => write!(f, "{}", n),
+            Expr::StringLiteral(_, s) => write!(f, "\"{}\"", s),
+            Expr::Set(object, name, value) => {
+                write!(f, "(set {} {} {})", object, name.lexeme, value)
+            }
+            Expr::Super(_, _, method) => write!(f, "(super {})", method.lexeme),
+            Expr::This(_, _) => write!(f, "this"),
+            Expr::Unary(op, expr) => write!(f, "({} {})", op.lexeme, expr),
+            Expr::Variable(_, name) => write!(f, "{}", name.lexeme),
+        }
+    }
+}
+
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stmt::Break => write!(f, "(break)"),
+            Stmt::Class(name, superclass, methods) => {
+                write!(f, "(class {}", name.lexeme)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {}", superclass)?;
+                }
+                for method in methods {
+                    write!(f, " {}", method)?;
+                }
+                write!(f, ")")
             }
-            Expr::Binary(left, op, right) => {
-                let _ = write!(f, "{} {} {}", op, left, right);
+            Stmt::ExprStmt(expr) => write!(f, "(; {})", expr),
+            Stmt::Function(name, params, body) => {
+                write!(f, "(fun {} (", name.lexeme)?;
+                let params: Vec<&str> = params.iter().map(|p| p.lexeme.as_str()).collect();
+                write!(f, "{})", params.join(" "))?;
+                for stmt in body {
+                    write!(f, " {}", stmt)?;
+                }
+                write!(f, ")")
             }
-            Expr::Grouping(expr) => {
-                let _ = write!(f, "group {}", expr);
+            Stmt::Print(expr) => write!(f, "(print {})", expr),
+            Stmt::Return(_, None) => write!(f, "(return)"),
+            Stmt::Return(_, Some(value)) => write!(f, "(return {})", value),
+            Stmt::VarDeclaration(name, None) => write!(f, "(var {})", name.lexeme),
+            Stmt::VarDeclaration(name, Some(init)) => write!(f, "(var {} {})", name.lexeme, init),
+            Stmt::Block(stmts) => {
+                write!(f, "(block")?;
+                for stmt in stmts {
+                    write!(f, " {}", stmt)?;
+                }
+                write!(f, ")")
             }
-            Expr::FalseLiteral => {
-                let _ = write!(f, "false");
+            Stmt::IfStmt(condition, then_branch, else_branch) => {
+                write!(f, "(if {} {}", condition, then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    write!(f, " {}", else_branch)?;
+                }
+                write!(f, ")")
             }
-            Expr::TrueLiteral => {
-                let _ = write!(f, "true");
+            Stmt::While(condition, body) => write!(f, "(while {} {})", condition, body),
+        }
+    }
+}
+
+/// Prints a program back out as Lox source that parses to the same tree.
+/// `for` loops come out in the `while` form the parser desugars them into.
+pub fn to_source(stmts: &[Stmt]) -> String {
+    let mut printer = SourcePrinter::default();
+    for stmt in stmts {
+        printer.stmt(stmt);
+        printer.out.push('\n');
+    }
+    printer.out
+}
+
+impl Expr {
+    /// The expression as Lox source. Grouping parentheses are kept from the
+    /// original program, which is enough to preserve precedence.
+    pub fn to_source(&self) -> String {
+        match self {
+            Expr::Assignment(_, name, value) => {
+                format!("{} = {}", name.lexeme, value.to_source())
             }
-            Expr::NilLiteral => {
-                let _ = write!(f, "{}", String::from("nil"));
+            Expr::Binary(left, op, right) | Expr::Logical(left, op, right) => {
+                format!("{} {} {}", left.to_source(), op.lexeme, right.to_source())
             }
-            Expr::NumberLiteral(n) => {
-                let _ = write!(f, "{}", n);
+            Expr::Call(callee, _, args) => {
+                let args: Vec<String> = args.iter().map(Expr::to_source).collect();
+                format!("{}({})", callee.to_source(), args.join(", "))
             }
-            Expr::StringLiteral(s) => {
-                let _ = write!(f, "{}", s);
+            Expr::Get(object, name) => format!("{}.{}", object.to_source(), name.lexeme),
+            Expr::Grouping(expr) => format!("({})", expr.to_source()),
+            Expr::TrueLiteral(_) => String::from("true"),
+            Expr::FalseLiteral(_) => String::from("false"),
+            Expr::NilLiteral(_) => String::from("nil"),
+            Expr::NumberLiteral(_, n) => n.to_string(),
+            Expr::StringLiteral(_, s) => {
+                format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
             }
-            Expr::Unary(op, expr) => {
-                let _ = write!(f, "{} {}", op, expr);
+            Expr::Set(object, name, value) => {
+                format!(
+                    "{}.{} = {}",
+                    object.to_source(),
+                    name.lexeme,
+                    value.to_source()
+                )
             }
-            Expr::Identifier(s) => {
-                let _ = write!(f, "{}", s);
+            Expr::Super(_, _, method) => format!("super.{}", method.lexeme),
+            Expr::This(_, _) => String::from("this"),
+            Expr::Unary(op, expr) => format!("{}{}", op.lexeme, expr.to_source()),
+            Expr::Variable(_, name) => name.lexeme.clone(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct SourcePrinter {
+    out: String,
+    indent: usize,
+}
+
+impl SourcePrinter {
+    // Writes `stmt` starting at the current position. Statements nested in
+    // blocks go on their own lines at the block's indentation.
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Break => self.out += "break;",
+            Stmt::Class(name, superclass, methods) => {
+                self.out += &format!("class {} ", name.lexeme);
+                if let Some(superclass) = superclass {
+                    self.out += &format!("< {} ", superclass.to_source());
+                }
+                self.block(methods, |printer, method| match method {
+                    Stmt::Function(name, params, body) => printer.function(name, params, body),
+                    _ => printer.stmt(method),
+                });
             }
-            Expr::Variable(identifier) => {
-                let _ = write!(f, "{}", identifier);
+            Stmt::ExprStmt(expr) => self.out += &format!("{};", expr.to_source()),
+            Stmt::Function(name, params, body) => {
+                self.out += "fun ";
+                self.function(name, params, body);
             }
-            Expr::Logical(lhs, op, rhs) => {
-                let _ = write!(f, "{} {} {}", lhs, op, rhs);
+            Stmt::Print(expr) => self.out += &format!("print {};", expr.to_source()),
+            Stmt::Return(_, None) => self.out += "return;",
+            Stmt::Return(_, Some(value)) => self.out += &format!("return {};", value.to_source()),
+            Stmt::VarDeclaration(name, None) => self.out += &format!("var {};", name.lexeme),
+            Stmt::VarDeclaration(name, Some(init)) => {
+                self.out += &format!("var {} = {};", name.lexeme, init.to_source())
             }
-            Expr::Call(callee, _, args) => {
-                let _ = write!(f, "{}({:?})", callee, args);
+            Stmt::Block(stmts) => self.block(stmts, SourcePrinter::stmt),
+            Stmt::IfStmt(condition, then_branch, else_branch) => {
+                self.out += &format!("if ({}) ", condition.to_source());
+                self.stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.out += " else ";
+                    self.stmt(else_branch);
+                }
             }
+            Stmt::While(condition, body) => {
+                self.out += &format!("while ({}) ", condition.to_source());
+                self.stmt(body);
+            }
+        }
+    }
+
+    fn function(&mut self, name: &Token, params: &[Token], body: &[Stmt]) {
+        let params: Vec<&str> = params.iter().map(|p| p.lexeme.as_str()).collect();
+        self.out += &format!("{}({}) ", name.lexeme, params.join(", "));
+        self.block(body, SourcePrinter::stmt);
+    }
+
+    fn block(&mut self, stmts: &[Stmt], mut print: impl FnMut(&mut Self, &Stmt)) {
+        if stmts.is_empty() {
+            self.out += "{}";
+            return;
+        }
+
+        self.out += "{\n";
+        self.indent += 1;
+        for stmt in stmts {
+            self.out += &"  ".repeat(self.indent);
+            print(self, stmt);
+            self.out.push('\n');
         }
-        write!(f, "{}", ")")
+        self.indent -= 1;
+        self.out += &"  ".repeat(self.indent);
+        self.out.push('}');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::{default_reserved, Scanner};
+    use crate::token::TokenType;
+
+    fn parse(source: &str) -> Vec<Stmt> {
+        let tokens = Scanner::new(default_reserved(), source)
+            .scan_tokens()
+            .unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    fn sexprs(stmts: &[Stmt]) -> Vec<String> {
+        stmts.iter().map(Stmt::to_string).collect()
+    }
+
+    #[test]
+    fn prints_expressions_as_s_expressions() {
+        let expr = Expr::Binary(
+            Box::new(Expr::Unary(
+                Token::new(TokenType::Minus, String::from("-"), 1, 1, None),
+                Box::new(Expr::NumberLiteral(
+                    Token::new(TokenType::Number, String::from("123"), 1, 2, None),
+                    123.0,
+                )),
+            )),
+            Token::new(TokenType::Star, String::from("*"), 1, 6, None),
+            Box::new(Expr::Grouping(Box::new(Expr::NumberLiteral(
+                Token::new(TokenType::Number, String::from("45.67"), 1, 9, None),
+                45.67,
+            )))),
+        );
+        assert_eq!(expr.to_string(), "(* (- 123) (group 45.67))");
+    }
+
+    #[test]
+    fn prints_statements_as_s_expressions() {
+        let stmts = parse("var a = 1; while (a < 3) { print a and \"x\"; a = a + 1; } f(a, nil);");
+        assert_eq!(
+            sexprs(&stmts),
+            vec![
+                "(var a 1)",
+                "(while (< a 3) (block (print (and a \"x\")) (; (= a (+ a 1)))))",
+                "(; (call f a nil))",
+            ]
+        );
+    }
+
+    #[test]
+    fn source_printer_round_trips_every_construct() {
+        let source = r#"
+class Base {
+  init(name) {
+    this.name = name;
+  }
+  greet() {
+    print "hi " + this.name;
+    return;
+  }
+}
+class Derived < Base {
+  greet() {
+    super.greet();
+    return this;
+  }
+}
+fun add(a, b) {
+  return a + -b * (2 - !true == false);
+}
+fun noop() {}
+print "say \"hi\" \\ bye";
+var x;
+var y = nil;
+x = Derived("lox").greet().name;
+{
+  if (x != nil or y) print x; else {
+    print "no";
+  }
+  while (true) {
+    if (y) break;
+    y = true;
+  }
+}
+for (var i = 0; i < 2; i = i + 1) print add(i, 1.5) >= 0 and i <= 1 or i > 0 / 1;
+"#;
+        let stmts = parse(source);
+        let printed = to_source(&stmts);
+        let reparsed = parse(&printed);
+
+        assert_eq!(sexprs(&reparsed), sexprs(&stmts));
+        assert_eq!(to_source(&reparsed), printed);
+    }
+
+    #[test]
+    fn source_printer_indents_blocks() {
+        let stmts = parse("fun f(a) { if (a) { print a; } }");
+        assert_eq!(
+            to_source(&stmts),
+            "fun f(a) {\n  if (a) {\n    print a;\n  }\n}\n"
+        );
     }
 }
@@ -0,0 +1,131 @@
+use super::error::LoxError;
+use super::function::Function;
+use super::interpreter::Interpreter;
+use super::token::Token;
+use super::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// A class created by evaluating a `class` declaration. Calling it constructs
+/// a new instance and runs the `init` method, if there is one. Methods not
+/// found on the class are looked up along the superclass chain.
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub superclass: Option<Rc<Class>>,
+    pub methods: HashMap<String, Rc<Function>>,
+}
+
+impl Class {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<Class>>,
+        methods: HashMap<String, Rc<Function>>,
+    ) -> Self {
+        Class {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    pub fn find_method(&self, name: &str) -> Option<Rc<Function>> {
+        if let Some(method) = self.methods.get(name) {
+            return Some(Rc::clone(method));
+        }
+
+        match &self.superclass {
+            Some(superclass) => superclass.find_method(name),
+            None => None,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self.find_method("init") {
+            Some(initializer) => initializer.arity(),
+            None => 0,
+        }
+    }
+
+    pub fn call(
+        class: &Rc<Class>,
+        interpreter: &mut Interpreter<Value>,
+        args: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        let instance = Rc::new(Instance::new(Rc::clone(class)));
+        if let Some(initializer) = class.find_method("init") {
+            initializer
+                .bind(Rc::clone(&instance))
+                .call(interpreter, args)?;
+        }
+        Ok(Value::Instance(instance))
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// An instance of a class. Fields are created on first assignment and shadow
+/// methods of the same name.
+pub struct Instance {
+    pub class: Rc<Class>,
+    pub fields: RefCell<HashMap<String, Value>>,
+}
+
+impl Instance {
+    pub fn new(class: Rc<Class>) -> Self {
+        Instance {
+            class,
+            fields: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(instance: &Rc<Instance>, name: &Token) -> Option<Value> {
+        if let Some(value) = instance.fields.borrow().get(&name.lexeme) {
+            return Some(value.clone());
+        }
+
+        instance
+            .class
+            .find_method(&name.lexeme)
+            .map(|method| Value::Function(Rc::new(method.bind(Rc::clone(instance)))))
+    }
+
+    pub fn set(&self, name: &Token, value: Value) {
+        self.fields.borrow_mut().insert(name.lexeme.clone(), value);
+    }
+
+    /// Every name `get` would find: the fields, then the methods of the class
+    /// and its superclasses, sorted and without duplicates.
+    pub fn member_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.fields.borrow().keys().cloned().collect();
+        let mut class = Some(&self.class);
+        while let Some(current) = class {
+            names.extend(current.methods.keys().cloned());
+            class = current.superclass.as_ref();
+        }
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+// Fields can refer back to the instance itself, so only the class is shown.
+impl fmt::Debug for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Instance")
+            .field("class", &self.class.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Instance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -0,0 +1,184 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use rustylox::{default_reserved, Environment, Value};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Tab completion for the REPL. Identifiers complete from the keywords and
+/// every binding visible from the interpreter's current environment; after a
+/// `.` they complete from the fields and methods of the instance on the left.
+pub struct LoxHelper {
+    pub environment: Rc<RefCell<Environment<Value>>>,
+}
+
+impl LoxHelper {
+    pub fn new(environment: Rc<RefCell<Environment<Value>>>) -> Self {
+        LoxHelper { environment }
+    }
+}
+
+impl Completer for LoxHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&self.environment, &line[..pos]))
+    }
+}
+
+impl Hinter for LoxHelper {
+    type Hint = String;
+}
+
+impl Highlighter for LoxHelper {}
+
+impl Validator for LoxHelper {}
+
+impl Helper for LoxHelper {}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+// Completes the identifier that ends `line`, returning where it starts and
+// the names it could be.
+fn complete(environment: &Rc<RefCell<Environment<Value>>>, line: &str) -> (usize, Vec<String>) {
+    let start = line
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !is_identifier_char(c))
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let prefix = &line[start..];
+
+    let names = match line[..start].strip_suffix('.') {
+        Some(object) => member_names(environment, object),
+        None => binding_names(environment),
+    };
+
+    let mut candidates: Vec<String> = names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+fn binding_names(environment: &Rc<RefCell<Environment<Value>>>) -> Vec<String> {
+    let mut names: Vec<String> = default_reserved()
+        .keys()
+        .map(|keyword| keyword.to_string())
+        .collect();
+
+    // The chain of enclosing scopes ends at the globals.
+    let mut scope = Some(Rc::clone(environment));
+    while let Some(current) = scope {
+        names.extend(current.borrow().values.keys().cloned());
+        scope = current.borrow().enclosing.clone();
+    }
+    names
+}
+
+// The members of the instance named by a chain like `a.b.c`, following
+// fields from a variable. Anything more involved, such as a call, isn't
+// evaluated and completes to nothing.
+fn member_names(environment: &Rc<RefCell<Environment<Value>>>, object: &str) -> Vec<String> {
+    let start = object
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| !is_identifier_char(c) && c != '.')
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let mut path = object[start..].split('.');
+
+    let mut value = path.next().and_then(|name| environment.borrow().get(name));
+    for field in path {
+        value = match value {
+            Some(Value::Instance(instance)) => instance.fields.borrow().get(field).cloned(),
+            _ => None,
+        };
+    }
+
+    match value {
+        Some(Value::Instance(instance)) => instance.member_names(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustylox::Lox;
+
+    fn completions(setup: &str, line: &str) -> (usize, Vec<String>) {
+        let mut lox = Lox::new();
+        lox.eval_str(setup).unwrap();
+        complete(&lox.interpreter().environment, line)
+    }
+
+    #[test]
+    fn completes_keywords_and_globals() {
+        let (start, candidates) = completions("var counter = 1; fun count() {}", "print co");
+        assert_eq!(start, 6);
+        assert_eq!(candidates, vec!["count", "counter"]);
+
+        let (_, candidates) = completions("", "whi");
+        assert_eq!(candidates, vec!["while"]);
+
+        let (_, candidates) = completions("", "cl");
+        assert_eq!(candidates, vec!["class", "clock"]);
+    }
+
+    #[test]
+    fn completes_bindings_from_enclosing_scopes() {
+        let mut lox = Lox::new();
+        lox.eval_str("var outer = 1;").unwrap();
+        let globals = Rc::clone(&lox.interpreter().globals);
+        let mut block = Environment::new(Some(globals));
+        block.define("inner", Value::Nil);
+        let block = Rc::new(RefCell::new(block));
+
+        let (_, candidates) = complete(&block, "in");
+        assert_eq!(candidates, vec!["inner"]);
+        let (_, candidates) = complete(&block, "ou");
+        assert_eq!(candidates, vec!["outer"]);
+    }
+
+    #[test]
+    fn completes_instance_members() {
+        let setup = "
+            class Base { greet() {} }
+            class Point < Base { init() { this.x = 1; } norm() {} }
+            var p = Point();
+            p.origin = Point();
+        ";
+        let (start, candidates) = completions(setup, "print p.");
+        assert_eq!(start, 8);
+        assert_eq!(candidates, vec!["greet", "init", "norm", "origin", "x"]);
+
+        let (_, candidates) = completions(setup, "p.origin.n");
+        assert_eq!(candidates, vec!["norm"]);
+
+        let (_, candidates) = completions(setup, "p.x.");
+        assert!(candidates.is_empty());
+    }
+
+    #[test]
+    fn handles_non_ascii_input() {
+        let (start, _) = completions("var value = 1;", "print \"é");
+        assert_eq!(start, 9);
+
+        let (start, candidates) = completions("var value = 1;", "print \"é\" + va");
+        assert_eq!(start, 13);
+        assert_eq!(candidates, vec!["value", "var"]);
+
+        let (_, candidates) = completions("var value = 1;", "é.va");
+        assert!(candidates.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use super::error::LoxError;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+
+/// Renders diagnostics against the source they were reported for, e.g.
+///
+/// ```text
+/// error[ParseError]: Expect ';' after value.
+///  --> script.lox:2:9
+///   |
+/// 2 | print 1 +
+///   |         ^
+///   = note: at end of input
+/// ```
+///
+/// Color is off by default; callers enable it when writing to a terminal.
+pub struct Renderer<'a> {
+    file_name: &'a str,
+    source: &'a str,
+    color: bool,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(file_name: &'a str, source: &'a str) -> Self {
+        Renderer {
+            file_name,
+            source,
+            color: false,
+        }
+    }
+
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn render_all(&self, diagnostics: &[LoxError]) -> String {
+        diagnostics
+            .iter()
+            .map(|err| self.render(err))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn render(&self, err: &LoxError) -> String {
+        let mut out = format!(
+            "{}: {}\n",
+            self.paint(&format!("error[{}]", err.kind.name()), BOLD_RED),
+            self.paint(&err.message, BOLD)
+        );
+
+        let span = err.span;
+        // Errors that don't point into the source (e.g. an unreadable file)
+        // have no snippet to show.
+        if span.line == 0 {
+            for note in &err.notes {
+                out += &format!("{} note: {}\n", self.paint("=", BOLD_BLUE), note);
+            }
+            return out;
+        }
+
+        let line_number = span.line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let bar = self.paint("|", BOLD_BLUE);
+        let line = self
+            .source
+            .lines()
+            .nth((span.line - 1) as usize)
+            .unwrap_or("");
+
+        out += &format!(
+            "{}{} {}:{}:{}\n",
+            gutter,
+            self.paint("-->", BOLD_BLUE),
+            self.file_name,
+            span.line,
+            span.start
+        );
+        out += &format!("{} {}\n", gutter, bar);
+        out += &format!("{} {} {}\n", self.paint(&line_number, BOLD_BLUE), bar, line);
+        out += &format!(
+            "{} {} {}{}\n",
+            gutter,
+            bar,
+            self.padding(line, span.start),
+            self.paint(&underline(span.start, span.end), BOLD_RED)
+        );
+        for note in &err.notes {
+            out += &format!("{} {} note: {}\n", gutter, self.paint("=", BOLD_BLUE), note);
+        }
+        out
+    }
+
+    // Whitespace up to the 1-based column, keeping tabs so the underline stays
+    // aligned with the source line above it.
+    fn padding(&self, line: &str, column: u32) -> String {
+        let mut chars = line.chars();
+        (1..column.max(1))
+            .map(|_| match chars.next() {
+                Some('\t') => '\t',
+                _ => ' ',
+            })
+            .collect()
+    }
+
+    fn paint(&self, text: &str, style: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+fn underline(start: u32, end: u32) -> String {
+    let width = end.saturating_sub(start) as usize;
+    format!("^{}", "~".repeat(width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{ErrorKind, Span};
+    use crate::parser::Parser;
+    use crate::scanner::{default_reserved, Scanner};
+
+    fn error(line: u32, start: u32, end: u32, message: &str) -> LoxError {
+        LoxError::new(
+            ErrorKind::RuntimeError,
+            Span::new(line, start, end),
+            message,
+        )
+    }
+
+    #[test]
+    fn underlines_a_single_column() {
+        let source = "var a = 1;\nprint -\"a\";";
+        let rendered =
+            Renderer::new("test.lox", source).render(&error(2, 7, 7, "Operand must be a number."));
+        assert_eq!(
+            rendered,
+            "error[RuntimeError]: Operand must be a number.\n \
+             --> test.lox:2:7\n  \
+             |\n\
+             2 | print -\"a\";\n  \
+             |       ^\n"
+        );
+    }
+
+    #[test]
+    fn underlines_every_column_of_a_span() {
+        let rendered = Renderer::new("test.lox", "print missing;").render(&error(
+            1,
+            7,
+            13,
+            "Undefined variable 'missing'.",
+        ));
+        assert!(
+            rendered.ends_with("1 | print missing;\n  |       ^~~~~~~\n"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn keeps_tabs_so_the_underline_lines_up() {
+        let rendered = Renderer::new("test.lox", "\tprint x;").render(&error(
+            1,
+            8,
+            8,
+            "Undefined variable 'x'.",
+        ));
+        assert!(
+            rendered.ends_with("1 | \tprint x;\n  | \t      ^\n"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn shows_notes_for_errors_at_end_of_input() {
+        let source = "print 1 +";
+        let tokens = Scanner::new(default_reserved(), source)
+            .scan_tokens()
+            .unwrap();
+        let errors = Parser::new(tokens).parse().unwrap_err();
+        let rendered = Renderer::new("test.lox", source).render(&errors[0]);
+        assert!(
+            rendered.starts_with("error[ParseError]: Expect expression.\n --> test.lox:1:"),
+            "{}",
+            rendered
+        );
+        assert!(
+            rendered.ends_with("  = note: at end of input\n"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn leaves_out_the_snippet_when_there_is_no_location() {
+        let err = LoxError::new(
+            ErrorKind::ReadFileError,
+            Span::default(),
+            "error reading file 'missing.lox'",
+        )
+        .with_note("No such file or directory");
+        assert_eq!(
+            Renderer::new("missing.lox", "").render(&err),
+            "error[ReadFileError]: error reading file 'missing.lox'\n\
+             = note: No such file or directory\n"
+        );
+    }
+
+    #[test]
+    fn colors_only_when_asked_to() {
+        let err = error(1, 1, 5, "Boom.");
+        let plain = Renderer::new("test.lox", "print").render(&err);
+        assert!(!plain.contains('\x1b'), "{:?}", plain);
+
+        let colored = Renderer::new("test.lox", "print")
+            .with_color(true)
+            .render(&err);
+        assert!(
+            colored.starts_with("\x1b[1;31merror[RuntimeError]\x1b[0m: \x1b[1mBoom.\x1b[0m\n"),
+            "{:?}",
+            colored
+        );
+        assert!(colored.contains("\x1b[1;31m^~~~~\x1b[0m"), "{:?}", colored);
+    }
+}
@@ -1,52 +1,74 @@
-use super::error::{error, ErrorKind};
 use super::value::Value;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 #[derive(Clone, Debug)]
 pub struct Environment<T> {
     pub values: HashMap<String, T>,
-    pub enclosing: Option<Box<Environment<T>>>,
+    pub enclosing: Option<Rc<RefCell<Environment<T>>>>,
 }
 
 impl Environment<Value> {
-    pub fn new(enclosing: Option<Box<Environment<Value>>>) -> Self {
+    pub fn new(enclosing: Option<Rc<RefCell<Environment<Value>>>>) -> Self {
         Environment {
             values: HashMap::new(),
-            enclosing: enclosing,
+            enclosing,
         }
     }
 
-    pub fn define(&mut self, name: &String, value: Value) {
-        self.values.insert(name.clone(), value);
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(name.to_string(), value);
     }
 
-    pub fn assign(&mut self, name: &String, value: Value) {
-        if let Some(_) = self.values.get(name.as_str()) {
-            self.values.insert(name.clone(), value.clone());
-            return;
+    // Returns false when no environment in the chain defines `name`.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        if self.values.contains_key(name) {
+            self.values.insert(name.to_string(), value);
+            return true;
         }
 
-        if let Some(enclosing) = &mut self.enclosing {
-            enclosing.assign(name, value.clone());
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => false,
         }
     }
 
-    pub fn get(&self, name: &str) -> Option<&Value> {
-        if let Some(value) = self.values.get(name) {
-            return Some(value);
+    // Walks exactly `distance` enclosing links, as computed by the resolver.
+    pub fn ancestor(env: &Rc<RefCell<Self>>, distance: usize) -> Rc<RefCell<Self>> {
+        let mut env = Rc::clone(env);
+        for _ in 0..distance {
+            let enclosing = match &env.borrow().enclosing {
+                Some(enclosing) => Rc::clone(enclosing),
+                None => panic!("Resolved scope depth exceeds the environment chain."),
+            };
+            env = enclosing;
         }
+        env
+    }
+
+    pub fn get_at(env: &Rc<RefCell<Self>>, distance: usize, name: &str) -> Option<Value> {
+        Environment::ancestor(env, distance)
+            .borrow()
+            .values
+            .get(name)
+            .cloned()
+    }
 
-        if let Some(enclosing) = &self.enclosing {
-            return enclosing.get(name);
+    pub fn assign_at(env: &Rc<RefCell<Self>>, distance: usize, name: &str, value: Value) {
+        Environment::ancestor(env, distance)
+            .borrow_mut()
+            .define(name, value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.values.get(name) {
+            return Some(value.clone());
         }
 
-        error(
-            0,
-            0,
-            0,
-            format!("undefined variable: {}", name),
-            ErrorKind::RuntimeError,
-        );
-        None
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow().get(name),
+            None => None,
+        }
     }
 }
@@ -1,48 +1,122 @@
-#[derive(Debug)]
+use super::token::Token;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum ErrorKind {
     ScanError,
     ReadFileError,
     ParseError,
+    ResolveError,
+    CompileError,
     EvaluatorError,
     RuntimeError,
 }
 
-impl std::fmt::Display for ErrorKind {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let error_kind = match self {
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
             ErrorKind::ScanError => "ScanError",
             ErrorKind::ReadFileError => "ReadFileError",
             ErrorKind::ParseError => "ParseError",
+            ErrorKind::ResolveError => "ResolveError",
+            ErrorKind::CompileError => "CompileError",
             ErrorKind::EvaluatorError => "EvaluatorError",
             ErrorKind::RuntimeError => "RuntimeError",
-        };
-        write!(f, "Error: {}", error_kind)
+        }
+    }
+
+    /// The sysexits(3) status a process should exit with after this kind of
+    /// error: 65 (EX_DATAERR) for a program that failed to compile, 70
+    /// (EX_SOFTWARE) for one that failed while running, and 74 (EX_IOERR) when
+    /// the program couldn't be read at all.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::ScanError
+            | ErrorKind::ParseError
+            | ErrorKind::ResolveError
+            | ErrorKind::CompileError => 65,
+            ErrorKind::EvaluatorError | ErrorKind::RuntimeError => 70,
+            ErrorKind::ReadFileError => 74,
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Error: {}", self.name())
     }
 }
 
-struct Error {
-    line: u32,
-    start: u32,
-    end: u32,
-    message: String,
-    kind: ErrorKind,
+/// The source location an error refers to: a line and the 1-based columns of
+/// the first and last characters of the offending text on that line.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub line: u32,
+    pub start: u32,
+    pub end: u32,
 }
 
-pub fn error(line: u32, start: u32, end: u32, message: String, kind: ErrorKind) {
-    let e = Error {
-        line,
-        start,
-        end,
-        message,
-        kind,
-    };
+impl Span {
+    pub fn new(line: u32, start: u32, end: u32) -> Self {
+        Span { line, start, end }
+    }
+}
 
-    report(e);
+// A token that spans lines, such as a multi-line string, is underlined up to
+// the end of its first line.
+impl From<&Token> for Span {
+    fn from(token: &Token) -> Self {
+        let first_line = token.lexeme.split('\n').next().unwrap_or("");
+        let width = first_line.chars().count() as u32;
+        Span::new(token.line, token.column, token.column + width.max(1) - 1)
+    }
 }
 
-fn report(err: Error) {
-    eprintln!(
-        "{}:{}-{} {}: {}",
-        err.line, err.start, err.end, err.kind, err.message
-    );
+/// An error produced by any stage of running a Lox program. Errors are
+/// returned rather than printed so that embedders decide how to present them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoxError {
+    pub kind: ErrorKind,
+    pub span: Span,
+    pub message: String,
+    pub notes: Vec<String>,
 }
+
+impl LoxError {
+    pub fn new(kind: ErrorKind, span: Span, message: impl Into<String>) -> Self {
+        LoxError {
+            kind,
+            span,
+            message: message.into(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn at_token(kind: ErrorKind, token: &Token, message: impl Into<String>) -> Self {
+        LoxError::new(kind, Span::from(token), message)
+    }
+
+    pub fn runtime(token: &Token, message: impl Into<String>) -> Self {
+        LoxError::at_token(ErrorKind::RuntimeError, token, message)
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+}
+
+impl std::fmt::Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}-{} {}: {}",
+            self.span.line, self.span.start, self.span.end, self.kind, self.message
+        )?;
+        for note in &self.notes {
+            write!(f, "\n  note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for LoxError {}
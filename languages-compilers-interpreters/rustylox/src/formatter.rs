@@ -0,0 +1,293 @@
+use super::error::LoxError;
+use super::parser::Parser;
+use super::scanner::{default_reserved, Scanner};
+use super::token::{Token, TokenType};
+
+/// Rewrites a program in canonical style: one statement per line, two-space
+/// indentation, braces on the line that opens them and single spaces around
+/// binary operators. Comments are kept, as are single blank lines between
+/// statements.
+///
+/// The formatter works on tokens rather than the AST so that the program
+/// keeps its original shape (a `for` loop stays a `for` loop). The program is
+/// still parsed first, and one with errors is returned untouched along with
+/// its diagnostics.
+pub fn format_source(source: &str) -> Result<String, Vec<LoxError>> {
+    let tokens = Scanner::new(default_reserved(), source)
+        .with_comments()
+        .scan_tokens()?;
+
+    let code = tokens
+        .iter()
+        .filter(|token| token.token_type != TokenType::Comment)
+        .cloned()
+        .collect();
+    Parser::new(code).parse()?;
+
+    let mut formatter = Formatter::default();
+    for (i, token) in tokens.iter().enumerate() {
+        if token.token_type == TokenType::EOF {
+            break;
+        }
+        formatter.token(token, tokens.get(i + 1));
+    }
+    Ok(formatter.finish())
+}
+
+// Strings and block comments can span lines.
+fn end_line(token: &Token) -> u32 {
+    token.line + token.lexeme.matches('\n').count() as u32
+}
+
+#[derive(Default)]
+struct Formatter {
+    out: String,
+    indent: usize,
+    paren_depth: usize,
+    // A line break is owed before the next token, e.g. after a `;`.
+    break_due: bool,
+    // The last code token left a statement unfinished, so a comment that
+    // breaks the line puts the rest of it on an indented continuation line.
+    mid_statement: bool,
+    previous: Option<Token>,
+    // Whether the previous token was a prefix `-` or `!`.
+    previous_unary: bool,
+}
+
+impl Formatter {
+    fn token(&mut self, token: &Token, next: Option<&Token>) {
+        let was_due = self.break_due;
+        let trailing_comment = token.token_type == TokenType::Comment
+            && self
+                .previous
+                .as_ref()
+                .is_some_and(|previous| end_line(previous) == token.line);
+        // `else` joins the `}` that closed a block then-branch.
+        let joins_line = trailing_comment
+            || (token.token_type == TokenType::Else && self.after(TokenType::RightBrace));
+
+        if token.token_type == TokenType::RightBrace {
+            self.indent = self.indent.saturating_sub(1);
+        }
+
+        if self.previous.is_none() {
+            // The first token starts the file.
+        } else if joins_line {
+            if !trailing_comment || self.space_before(token) {
+                self.out.push(' ');
+            }
+        } else if self.break_due || token.token_type == TokenType::Comment {
+            // Comments that don't trail code go on their own line.
+            self.new_line(token);
+        } else if self.space_before(token) {
+            self.out.push(' ');
+        }
+
+        self.out += &token.lexeme;
+        self.break_due = false;
+
+        match token.token_type {
+            TokenType::LeftParen => self.paren_depth += 1,
+            TokenType::RightParen => self.paren_depth = self.paren_depth.saturating_sub(1),
+            TokenType::LeftBrace => {
+                self.indent += 1;
+                // Leave empty bodies as `{}`.
+                self.break_due = next.is_none_or(|t| t.token_type != TokenType::RightBrace);
+            }
+            TokenType::RightBrace => self.break_due = true,
+            // A line comment runs to the end of its line; a block comment
+            // only ends one if the code after it was on a later line, or if
+            // it trails code that ended one, such as a `;`.
+            TokenType::Comment => {
+                self.break_due = (trailing_comment && was_due)
+                    || token.lexeme.starts_with("//")
+                    || next.is_none_or(|t| t.line > end_line(token))
+            }
+            // Inside a `for` clause, `;` separates the clauses on one line.
+            TokenType::Semicolon => self.break_due = self.paren_depth == 0,
+            _ => {}
+        }
+        if token.token_type != TokenType::Comment {
+            self.mid_statement = !self.break_due;
+        }
+
+        self.previous_unary = self.is_unary(token);
+        self.previous = Some(token.clone());
+    }
+
+    fn after(&self, token_type: TokenType) -> bool {
+        self.previous
+            .as_ref()
+            .is_some_and(|previous| previous.token_type == token_type)
+    }
+
+    fn new_line(&mut self, token: &Token) {
+        let previous = self.previous.as_ref().unwrap();
+        let blank_line = token.line > end_line(previous) + 1
+            && previous.token_type != TokenType::LeftBrace
+            && token.token_type != TokenType::RightBrace;
+
+        self.out.push('\n');
+        if blank_line {
+            self.out.push('\n');
+        }
+        self.out += &"  ".repeat(self.indent + self.mid_statement as usize);
+    }
+
+    fn space_before(&self, token: &Token) -> bool {
+        let previous = self.previous.as_ref().unwrap();
+        if self.previous_unary {
+            return false;
+        }
+        match (&previous.token_type, &token.token_type) {
+            (TokenType::LeftParen, _) | (TokenType::Dot, _) => return false,
+            (TokenType::LeftBrace, TokenType::RightBrace) => return false,
+            _ => {}
+        }
+        match token.token_type {
+            TokenType::Semicolon | TokenType::Comma | TokenType::Dot | TokenType::RightParen => {
+                false
+            }
+            // A call's arguments sit right against the callee.
+            TokenType::LeftParen => !matches!(
+                previous.token_type,
+                TokenType::Identifier | TokenType::RightParen
+            ),
+            _ => true,
+        }
+    }
+
+    fn is_unary(&self, token: &Token) -> bool {
+        match token.token_type {
+            TokenType::Bang => true,
+            TokenType::Minus => !self.previous.as_ref().is_some_and(|previous| {
+                matches!(
+                    previous.token_type,
+                    TokenType::Identifier
+                        | TokenType::Number
+                        | TokenType::String
+                        | TokenType::RightParen
+                        | TokenType::True
+                        | TokenType::False
+                        | TokenType::Nil
+                        | TokenType::This
+                )
+            }),
+            _ => false,
+        }
+    }
+
+    fn finish(mut self) -> String {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn assert_formats(source: &str, expected: &str) {
+        assert_eq!(format_source(source).unwrap(), expected);
+        assert_eq!(format_source(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn puts_one_statement_on_each_line() {
+        assert_formats(
+            "var a=1;print a;  a=a+1 ;",
+            "var a = 1;\nprint a;\na = a + 1;\n",
+        );
+    }
+
+    #[test]
+    fn indents_blocks_and_keeps_braces_on_the_opening_line() {
+        assert_formats(
+            "class A<B{init(x){this.x=x;}} fun f(){}\nif(!a) {\nprint -1;\n}\nelse{print f(a,b).c;}",
+            "class A < B {\n  init(x) {\n    this.x = x;\n  }\n}\nfun f() {}\n\
+             if (!a) {\n  print -1;\n} else {\n  print f(a, b).c;\n}\n",
+        );
+    }
+
+    #[test]
+    fn keeps_for_clauses_on_one_line() {
+        assert_formats(
+            "for(var i=0;i<2;i=i+1)print i-1;\nfor(;;)break;",
+            "for (var i = 0; i < 2; i = i + 1) print i - 1;\nfor (;;) break;\n",
+        );
+    }
+
+    #[test]
+    fn keeps_comments_and_single_blank_lines() {
+        assert_formats(
+            "// header\nvar a = 1;   // trailing\n\n\n\n{\n\n  // inside\n  print a;\n}\n",
+            "// header\nvar a = 1; // trailing\n\n{\n  // inside\n  print a;\n}\n",
+        );
+        assert_formats(
+            "if (a) { print a; } // then\nelse print b;",
+            "if (a) {\n  print a;\n} // then\nelse print b;\n",
+        );
+        assert_formats(
+            "/* multi\n   line */\nprint \"a\nb\";\n\nprint f(/* x */ 1);",
+            "/* multi\n   line */\nprint \"a\nb\";\n\nprint f(/* x */ 1);\n",
+        );
+        assert_formats(
+            "var a = 1; /* c */ var b = 2;\n{ /* d */ print a; }",
+            "var a = 1; /* c */\nvar b = 2;\n{ /* d */\n  print a;\n}\n",
+        );
+    }
+
+    #[test]
+    fn leaves_programs_with_errors_alone() {
+        let errors = format_source("print 1 +;").unwrap_err();
+        assert_eq!(errors[0].message, "Expect expression.");
+    }
+
+    #[test]
+    fn indents_statements_continued_after_a_comment() {
+        assert_formats(
+            "var a = 1 + // c\n2;\n{\nprint a /* d */\n+ 1;\n}",
+            "var a = 1 + // c\n  2;\n{\n  print a /* d */\n    + 1;\n}\n",
+        );
+    }
+
+    #[test]
+    fn puts_else_on_its_own_line_after_a_statement() {
+        assert_formats(
+            "if (a) print a; else print b;",
+            "if (a) print a;\nelse print b;\n",
+        );
+        assert_formats(
+            "if (a) { print a; } else if (b) print b; else { print c; }",
+            "if (a) {\n  print a;\n} else if (b) print b;\nelse {\n  print c;\n}\n",
+        );
+    }
+
+    #[test]
+    fn formatting_is_idempotent_on_the_test_suite() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox");
+        let mut dirs = vec![dir];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                let source = fs::read_to_string(&path).unwrap();
+                if let Ok(formatted) = format_source(&source) {
+                    assert_eq!(
+                        format_source(&formatted).unwrap(),
+                        formatted,
+                        "{}",
+                        path.display()
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use super::ast::{ExprId, Stmt};
+use super::class::Instance;
+use super::environment::Environment;
+use super::error::{ErrorKind, LoxError, Span};
+use super::interpreter::{ControlFlow, Interpreter};
+use super::token::Token;
+use super::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A user-defined function created by evaluating a `fun` declaration or a
+/// method in a class body. The closure is a live handle to the environment the
+/// declaration was evaluated in, so captured variables are shared rather than
+/// copied. `locals` holds the scope depths the resolver computed for the
+/// program the declaration came from.
+pub struct Function {
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Rc<Vec<Stmt>>,
+    pub closure: Rc<RefCell<Environment<Value>>>,
+    pub locals: Rc<HashMap<ExprId, usize>>,
+    pub is_initializer: bool,
+}
+
+impl Function {
+    pub fn new(
+        name: Token,
+        params: Vec<Token>,
+        body: Vec<Stmt>,
+        closure: Rc<RefCell<Environment<Value>>>,
+        locals: Rc<HashMap<ExprId, usize>>,
+        is_initializer: bool,
+    ) -> Self {
+        Function {
+            name,
+            params,
+            body: Rc::new(body),
+            closure,
+            locals,
+            is_initializer,
+        }
+    }
+
+    // Produces a method whose closure defines `this` as the given instance.
+    pub fn bind(&self, instance: Rc<Instance>) -> Function {
+        let mut env = Environment::new(Some(Rc::clone(&self.closure)));
+        env.define("this", Value::Instance(instance));
+        Function {
+            name: self.name.clone(),
+            params: self.params.clone(),
+            body: Rc::clone(&self.body),
+            closure: Rc::new(RefCell::new(env)),
+            locals: Rc::clone(&self.locals),
+            is_initializer: self.is_initializer,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.params.len()
+    }
+
+    // Each call gets a fresh environment holding the parameters, enclosed by
+    // the closure rather than by whatever scope the call happens in.
+    pub fn call(
+        &self,
+        interpreter: &mut Interpreter<Value>,
+        args: Vec<Value>,
+    ) -> Result<Value, LoxError> {
+        let mut env = Environment::new(Some(Rc::clone(&self.closure)));
+        for (param, arg) in self.params.iter().zip(args) {
+            env.define(&param.lexeme, arg);
+        }
+
+        if interpreter.call_depth >= interpreter.max_call_depth {
+            return Err(LoxError::new(
+                ErrorKind::RuntimeError,
+                Span::default(),
+                "Stack overflow.",
+            ));
+        }
+        interpreter.call_depth += 1;
+        let locals = std::mem::replace(&mut interpreter.locals, Rc::clone(&self.locals));
+        let result = interpreter.execute_block(&self.body, env);
+        interpreter.locals = locals;
+        interpreter.call_depth -= 1;
+        let result = result?;
+
+        // Initializers always hand back the instance, even on an early `return;`.
+        if self.is_initializer {
+            return Ok(Environment::get_at(&self.closure, 0, "this").unwrap_or(Value::Nil));
+        }
+
+        match result {
+            ControlFlow::Return(value) => Ok(value),
+            _ => Ok(Value::Nil),
+        }
+    }
+}
+
+// The closure can (and for recursive functions, does) contain the function
+// itself, so it is left out to keep the output finite.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<fn {}>", self.name.lexeme)
+    }
+}
+
+/// The signature of a function implemented in Rust and exposed to scripts. The
+/// same function can be installed on either backend, with
+/// `Interpreter::define_native` or `Vm::define_native`.
+pub type NativeFn = fn(&[Value]) -> Result<Value, LoxError>;
+
+/// A host function installed with `Interpreter::define_native`.
+#[derive(Debug)]
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: usize, function: NativeFn) -> Self {
+        NativeFunction {
+            name: name.to_string(),
+            arity,
+            function,
+        }
+    }
+
+    pub fn call(&self, args: &[Value]) -> Result<Value, LoxError> {
+        (self.function)(args)
+    }
+}
+
+impl fmt::Display for NativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn {}>", self.name)
+    }
+}
+
+// Seconds since the Unix epoch, for timing scripts.
+pub fn clock(_: &[Value]) -> Result<Value, LoxError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(Value::Number(now.as_secs_f64()))
+}
@@ -1,129 +1,307 @@
-use super::ast::{Expr, Stmt};
+use super::ast::{Expr, ExprId, Stmt};
+use super::class::{Class, Instance};
 use super::environment::Environment;
-use super::error::{error, ErrorKind};
-use super::token::TokenType;
+use super::error::{ErrorKind, LoxError, Span};
+use super::function::{clock, Function, NativeFn, NativeFunction};
+use super::output::Output;
+use super::token::{Token, TokenType};
 use super::value::Value;
+use super::MAX_CALL_DEPTH;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-#[derive(Clone, Debug)]
+// How a statement finished: normally, with the value of an expression
+// statement, or by a `break` or `return` unwinding to the enclosing loop or
+// call.
+pub(crate) enum ControlFlow {
+    Normal(Value),
+    Break,
+    Return(Value),
+}
+
+#[derive(Debug)]
 pub struct Interpreter<T> {
-    pub environment: Environment<T>,
-    pub globals: Environment<T>,
+    pub environment: Rc<RefCell<Environment<T>>>,
+    pub globals: Rc<RefCell<Environment<T>>>,
+    // Scope depths for the program being evaluated. Functions keep the table
+    // of the program that declared them and swap it in while they run.
+    pub(crate) locals: Rc<HashMap<ExprId, usize>>,
+    output: Output,
+    // How many Lox function calls are in progress, so that runaway recursion
+    // is reported instead of overflowing the native stack.
+    pub(crate) call_depth: usize,
+    pub(crate) max_call_depth: usize,
 }
 
 impl Interpreter<Value> {
     pub fn new(env: Environment<Value>) -> Interpreter<Value> {
-        Interpreter {
-            environment: env,
-            globals: Environment::new(None),
-        }
+        let globals = Rc::new(RefCell::new(env));
+        let mut interpreter = Interpreter {
+            environment: Rc::clone(&globals),
+            globals,
+            locals: Rc::default(),
+            output: Output::default(),
+            call_depth: 0,
+            max_call_depth: MAX_CALL_DEPTH,
+        };
+        interpreter.define_native("clock", 0, clock);
+        interpreter
+    }
+
+    /// Sends the text of `print` statements somewhere other than stdout.
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    /// Sets how deeply function calls can nest before a program fails with
+    /// "Stack overflow.". Each call uses native stack, so raise this only when
+    /// evaluating on a thread with a larger stack.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Installs a Rust function as a global that scripts can call. Errors
+    /// returned without a source location are reported at the call site.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = NativeFunction::new(name, arity, function);
+        self.globals
+            .borrow_mut()
+            .define(name, Value::NativeFunction(Rc::new(native)));
     }
 
-    pub fn evaluate(&mut self, stmts: &Vec<Stmt>) -> Result<Value, ()> {
+    // Records scope depths computed by the resolver for the next program to
+    // be evaluated. Functions from earlier programs (e.g. REPL inputs) hold on
+    // to their own depths, so those of a program nothing refers to any more
+    // are dropped.
+    pub fn resolve(&mut self, locals: HashMap<ExprId, usize>) {
+        self.locals = Rc::new(locals);
+    }
+
+    fn look_up_variable(&self, id: ExprId, name: &Token) -> Result<Value, LoxError> {
+        let value = match self.locals.get(&id) {
+            Some(distance) => Environment::get_at(&self.environment, *distance, &name.lexeme),
+            None => self.globals.borrow().get(&name.lexeme),
+        };
+        value.ok_or_else(|| {
+            LoxError::runtime(name, format!("Undefined variable '{}'.", name.lexeme))
+        })
+    }
+
+    pub fn evaluate(&mut self, stmts: &[Stmt]) -> Result<Value, LoxError> {
         let mut result = Value::Nil;
         for stmt in stmts {
-            result = self.evaluate_stmt(stmt)?;
+            result = match self.evaluate_stmt(stmt)? {
+                ControlFlow::Normal(value) => value,
+                // The resolver rejects `break` and `return` outside a loop or
+                // function, so neither reaches the top level.
+                ControlFlow::Break | ControlFlow::Return(_) => break,
+            };
         }
         Ok(result)
     }
 
     pub fn is_truthy(value: &Value) -> bool {
-        match value {
-            Value::Nil => false,
-            Value::False => false,
-            _ => true,
+        !matches!(value, Value::Nil | Value::False)
+    }
+
+    fn number_operand(op: &Token, operand: Value) -> Result<f64, LoxError> {
+        let n: Option<f64> = operand.into();
+        n.ok_or_else(|| LoxError::runtime(op, "Operand must be a number."))
+    }
+
+    fn number_operands(op: &Token, left: Value, right: Value) -> Result<(f64, f64), LoxError> {
+        let l: Option<f64> = left.into();
+        let r: Option<f64> = right.into();
+        match (l, r) {
+            (Some(l), Some(r)) => Ok((l, r)),
+            _ => Err(LoxError::runtime(op, "Operands must be numbers.")),
         }
     }
 
-    fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, ()> {
+    fn evaluate_expr(&mut self, expr: &Expr) -> Result<Value, LoxError> {
         match expr {
-            Expr::Assignment(name, value) => {
-                let result = self.evaluate_expr(&*value)?;
-                self.environment.assign(&name.lexeme, result.clone());
+            Expr::Assignment(id, name, value) => {
+                let result = self.evaluate_expr(value)?;
+                match self.locals.get(id) {
+                    Some(distance) => Environment::assign_at(
+                        &self.environment,
+                        *distance,
+                        &name.lexeme,
+                        result.clone(),
+                    ),
+                    None => {
+                        let assigned = self
+                            .globals
+                            .borrow_mut()
+                            .assign(&name.lexeme, result.clone());
+                        if !assigned {
+                            return Err(LoxError::runtime(
+                                name,
+                                format!("Undefined variable '{}'.", name.lexeme),
+                            ));
+                        }
+                    }
+                }
                 Ok(result)
             }
             Expr::Binary(l, op, r) => {
-                // To correctly implement this, I can use combinators that attempt to resolve an expr to a value for a given type,
-                // and continue until no more possible values are possible (which results in an error).
-                let l_result: Option<f64> = self.evaluate_expr(&*l)?.into();
-                let r_result: Option<f64> = self.evaluate_expr(&*r)?.into();
+                let left = self.evaluate_expr(l)?;
+                let right = self.evaluate_expr(r)?;
                 match op.token_type {
-                    TokenType::Plus => Ok(Value::Number(l_result.unwrap() + r_result.unwrap())),
-                    TokenType::Minus => Ok(Value::Number(l_result.unwrap() - r_result.unwrap())),
-                    TokenType::Star => Ok(Value::Number(l_result.unwrap() * r_result.unwrap())),
-                    TokenType::Slash => Ok(Value::Number(l_result.unwrap() / r_result.unwrap())),
-                    TokenType::BangEqual => Ok(match l_result.unwrap() != r_result.unwrap() {
-                        true => Value::True,
-                        false => Value::False,
-                    }),
-                    TokenType::EqualEqual => Ok(match l_result.unwrap() == r_result.unwrap() {
-                        true => Value::True,
-                        false => Value::False,
-                    }),
-                    TokenType::Less => Ok(match l_result.unwrap() < r_result.unwrap() {
-                        true => Value::True,
-                        false => Value::False,
-                    }),
-                    TokenType::LessEqual => Ok(match l_result.unwrap() <= r_result.unwrap() {
-                        true => Value::True,
-                        false => Value::False,
-                    }),
-                    _ => {
-                        error(
-                            op.line,
-                            op.column,
-                            op.column,
-                            String::from("Unknown operator error"),
-                            ErrorKind::EvaluatorError,
-                        );
-                        Err(())
+                    TokenType::Plus => match (left, right) {
+                        (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+                        (Value::String(l), Value::String(r)) => Ok(Value::String(l + &r)),
+                        _ => Err(LoxError::runtime(
+                            op,
+                            "Operands must be two numbers or two strings.",
+                        )),
+                    },
+                    TokenType::Minus => {
+                        let (l, r) = Interpreter::number_operands(op, left, right)?;
+                        Ok(Value::Number(l - r))
+                    }
+                    TokenType::Star => {
+                        let (l, r) = Interpreter::number_operands(op, left, right)?;
+                        Ok(Value::Number(l * r))
+                    }
+                    TokenType::Slash => {
+                        let (l, r) = Interpreter::number_operands(op, left, right)?;
+                        Ok(Value::Number(l / r))
+                    }
+                    TokenType::BangEqual => Ok(Value::from(!left.equals(&right))),
+                    TokenType::EqualEqual => Ok(Value::from(left.equals(&right))),
+                    TokenType::Greater => {
+                        let (l, r) = Interpreter::number_operands(op, left, right)?;
+                        Ok(Value::from(l > r))
                     }
+                    TokenType::GreaterEqual => {
+                        let (l, r) = Interpreter::number_operands(op, left, right)?;
+                        Ok(Value::from(l >= r))
+                    }
+                    TokenType::Less => {
+                        let (l, r) = Interpreter::number_operands(op, left, right)?;
+                        Ok(Value::from(l < r))
+                    }
+                    TokenType::LessEqual => {
+                        let (l, r) = Interpreter::number_operands(op, left, right)?;
+                        Ok(Value::from(l <= r))
+                    }
+                    _ => Err(LoxError::at_token(
+                        ErrorKind::EvaluatorError,
+                        op,
+                        "Unknown operator error",
+                    )),
                 }
             }
             Expr::Call(callee, paren, args) => {
-                let callee = self.evaluate_expr(&*callee)?;
+                let callee = self.evaluate_expr(callee)?;
                 let mut args_result = Vec::new();
                 for arg in args {
-                    args_result.push(self.evaluate_expr(&*arg)?);
+                    args_result.push(self.evaluate_expr(arg)?);
                 }
 
-                Err(())
-            }
-            Expr::Grouping(expr) => self.evaluate_expr(&*expr),
-            Expr::TrueLiteral => Ok(Value::True),
-            Expr::FalseLiteral => Ok(Value::False),
-            Expr::NumberLiteral(n) => Ok(Value::Number(*n)),
-            Expr::StringLiteral(s) => Ok(Value::String((*s).clone())),
-            Expr::Identifier(id) => Ok(Value::String((*id).clone())),
-            Expr::NilLiteral => Ok(Value::Nil),
-            Expr::Unary(op, expr) => match op.token_type {
-                TokenType::Plus => {
-                    let unary: Option<f64> = self.evaluate_expr(&*expr)?.into();
-                    Ok(Value::Number(-unary.unwrap()))
-                }
-                TokenType::Bang => {
-                    let unary: Option<bool> = self.evaluate_expr(&*expr)?.into();
-                    match !unary.unwrap() {
-                        true => Ok(Value::True),
-                        false => Ok(Value::False),
+                let arity = match &callee {
+                    Value::Function(function) => function.arity(),
+                    Value::NativeFunction(native) => native.arity,
+                    Value::Class(class) => class.arity(),
+                    _ => {
+                        return Err(LoxError::runtime(
+                            paren,
+                            "Can only call functions and classes.",
+                        ));
                     }
+                };
+
+                if args_result.len() != arity {
+                    return Err(LoxError::runtime(
+                        paren,
+                        format!(
+                            "Expected {} arguments but got {}.",
+                            arity,
+                            args_result.len()
+                        ),
+                    ));
                 }
-                _ => {
-                    error(
-                        0,
-                        0,
-                        0,
-                        String::from("Unknown unary operator error"),
-                        ErrorKind::EvaluatorError,
-                    );
-                    Err(())
+
+                let result = match callee {
+                    Value::Class(class) => Class::call(&class, self, args_result),
+                    Value::Function(function) => function.call(self, args_result),
+                    Value::NativeFunction(native) => native.call(&args_result),
+                    _ => unreachable!(),
+                };
+                // Errors without a source location, from natives or from a
+                // call nested too deeply, are reported at the call site.
+                result.map_err(|mut err| {
+                    if err.span.line == 0 {
+                        err.span = Span::from(paren);
+                    }
+                    err
+                })
+            }
+            Expr::Get(object, name) => match self.evaluate_expr(object)? {
+                Value::Instance(instance) => match Instance::get(&instance, name) {
+                    Some(value) => Ok(value),
+                    None => Err(LoxError::runtime(
+                        name,
+                        format!("Undefined property '{}'.", name.lexeme),
+                    )),
+                },
+                _ => Err(LoxError::runtime(name, "Only instances have properties.")),
+            },
+            Expr::Set(object, name, value) => match self.evaluate_expr(object)? {
+                Value::Instance(instance) => {
+                    let value = self.evaluate_expr(value)?;
+                    instance.set(name, value.clone());
+                    Ok(value)
                 }
+                _ => Err(LoxError::runtime(name, "Only instances have fields.")),
             },
-            Expr::Variable(name) => {
-                if let Some(value) = self.environment.get(&name.lexeme) {
-                    return Ok((*value).clone());
+            Expr::Super(id, keyword, method) => {
+                // `this` lives in the scope just inside the one binding `super`.
+                let distance = match self.locals.get(id) {
+                    Some(distance) => *distance,
+                    None => return Err(LoxError::runtime(keyword, "Unresolved use of 'super'.")),
+                };
+                let superclass = Environment::get_at(&self.environment, distance, "super");
+                let object = Environment::get_at(&self.environment, distance - 1, "this");
+
+                match (superclass, object) {
+                    (Some(Value::Class(superclass)), Some(Value::Instance(instance))) => {
+                        match superclass.find_method(&method.lexeme) {
+                            Some(function) => Ok(Value::Function(Rc::new(function.bind(instance)))),
+                            None => Err(LoxError::runtime(
+                                method,
+                                format!("Undefined property '{}'.", method.lexeme),
+                            )),
+                        }
+                    }
+                    _ => Err(LoxError::runtime(keyword, "Invalid use of 'super'.")),
+                }
+            }
+            Expr::This(id, keyword) => self.look_up_variable(*id, keyword),
+            Expr::Grouping(expr) => self.evaluate_expr(expr),
+            Expr::TrueLiteral(_) => Ok(Value::True),
+            Expr::FalseLiteral(_) => Ok(Value::False),
+            Expr::NumberLiteral(_, n) => Ok(Value::Number(*n)),
+            Expr::StringLiteral(_, s) => Ok(Value::String((*s).clone())),
+            Expr::NilLiteral(_) => Ok(Value::Nil),
+            Expr::Unary(op, expr) => {
+                let right = self.evaluate_expr(expr)?;
+                match op.token_type {
+                    TokenType::Minus => {
+                        let n = Interpreter::number_operand(op, right)?;
+                        Ok(Value::Number(-n))
+                    }
+                    TokenType::Bang => Ok(Value::from(!Interpreter::is_truthy(&right))),
+                    _ => Err(LoxError::at_token(
+                        ErrorKind::EvaluatorError,
+                        op,
+                        "Unknown unary operator error",
+                    )),
                 }
-                Err(())
             }
+            Expr::Variable(id, name) => self.look_up_variable(*id, name),
             Expr::Logical(left, op, right) => {
                 let left_result = self.evaluate_expr(left)?;
                 match op.token_type {
@@ -142,54 +320,95 @@ impl Interpreter<Value> {
                 let right_result = self.evaluate_expr(right)?;
                 Ok(right_result)
             }
-            _ => {
-                error(
-                    0,
-                    0,
-                    0,
-                    String::from("Unknown expression error"),
-                    ErrorKind::EvaluatorError,
-                );
-                Err(())
-            }
         }
     }
 
-    fn evaluate_stmt(&mut self, stmt: &Stmt) -> Result<Value, ()> {
+    fn evaluate_stmt(&mut self, stmt: &Stmt) -> Result<ControlFlow, LoxError> {
         match stmt {
             Stmt::Print(expr) => {
-                println!("{}", self.evaluate_expr(expr)?);
-                Ok(Value::Nil)
+                let value = self.evaluate_expr(expr)?;
+                self.output.print(&value.to_string())?;
+                Ok(ControlFlow::Normal(Value::Nil))
+            }
+            Stmt::Class(name, superclass, declarations) => {
+                let superclass = match superclass {
+                    Some(expr @ Expr::Variable(_, superclass_name)) => {
+                        match self.evaluate_expr(expr)? {
+                            Value::Class(class) => Some(class),
+                            _ => {
+                                return Err(LoxError::runtime(
+                                    superclass_name,
+                                    "Superclass must be a class.",
+                                ));
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+
+                // Methods of a subclass close over an environment binding `super`.
+                let mut closure = Rc::clone(&self.environment);
+                if let Some(superclass) = &superclass {
+                    let mut env = Environment::new(Some(Rc::clone(&self.environment)));
+                    env.define("super", Value::Class(Rc::clone(superclass)));
+                    closure = Rc::new(RefCell::new(env));
+                }
+
+                let mut methods = HashMap::new();
+                for declaration in declarations {
+                    if let Stmt::Function(name, params, body) = declaration {
+                        let method = Function::new(
+                            name.clone(),
+                            params.clone(),
+                            body.clone(),
+                            Rc::clone(&closure),
+                            Rc::clone(&self.locals),
+                            name.lexeme == "init",
+                        );
+                        methods.insert(name.lexeme.clone(), Rc::new(method));
+                    }
+                }
+
+                let class = Class::new(name.lexeme.clone(), superclass, methods);
+                self.environment
+                    .borrow_mut()
+                    .define(&name.lexeme, Value::Class(Rc::new(class)));
+                Ok(ControlFlow::Normal(Value::Nil))
+            }
+            Stmt::ExprStmt(expr) => Ok(ControlFlow::Normal(self.evaluate_expr(expr)?)),
+            Stmt::Function(name, params, body) => {
+                let function = Function::new(
+                    name.clone(),
+                    params.clone(),
+                    body.clone(),
+                    Rc::clone(&self.environment),
+                    Rc::clone(&self.locals),
+                    false,
+                );
+                self.environment
+                    .borrow_mut()
+                    .define(&name.lexeme, Value::Function(Rc::new(function)));
+                Ok(ControlFlow::Normal(Value::Nil))
+            }
+            Stmt::Return(_, expr) => {
+                let mut value = Value::Nil;
+                if let Some(expr) = expr {
+                    value = self.evaluate_expr(expr)?;
+                }
+                Ok(ControlFlow::Return(value))
             }
-            Stmt::ExprStmt(expr) => self.evaluate_expr(expr),
             Stmt::VarDeclaration(name, expr) => {
                 let mut value = Value::Nil;
                 if let Some(initializer) = expr {
-                    value = self.evaluate_expr(&*initializer)?;
+                    value = self.evaluate_expr(initializer)?;
                 }
-                self.environment.define(&name.lexeme, value);
-                Ok(Value::Nil)
+                self.environment.borrow_mut().define(&name.lexeme, value);
+                Ok(ControlFlow::Normal(Value::Nil))
             }
-            Stmt::Break => Ok(Value::Break),
+            Stmt::Break => Ok(ControlFlow::Break),
             Stmt::Block(stmts) => {
-                self.environment = Environment::new(Some(Box::new(self.environment.clone())));
-                let mut result = Ok(Value::Nil);
-                'outer: for stmt in stmts {
-                    match self.evaluate_stmt(stmt) {
-                        v @ Ok(Value::Break) => {
-                            result = v;
-                            break 'outer;
-                        }
-                        _ => {}
-                    }
-                }
-
-                self.environment = match self.environment.enclosing.clone() {
-                    Some(env) => *env,
-                    None => panic!("The impossible happened."),
-                };
-
-                result
+                let env = Environment::new(Some(Rc::clone(&self.environment)));
+                self.execute_block(stmts, env)
             }
             Stmt::IfStmt(condition, then_branch, else_branch) => {
                 if Interpreter::is_truthy(&self.evaluate_expr(condition)?) {
@@ -197,18 +416,256 @@ impl Interpreter<Value> {
                 } else if let Some(else_branch) = else_branch {
                     self.evaluate_stmt(else_branch)
                 } else {
-                    Ok(Value::Nil)
+                    Ok(ControlFlow::Normal(Value::Nil))
                 }
             }
             Stmt::While(condition, body) => {
                 while Interpreter::is_truthy(&self.evaluate_expr(condition)?) {
-                    match self.evaluate_stmt(body) {
-                        Ok(Value::Break) => break,
-                        _ => {}
+                    match self.evaluate_stmt(body)? {
+                        ControlFlow::Break => break,
+                        flow @ ControlFlow::Return(_) => return Ok(flow),
+                        ControlFlow::Normal(_) => {}
                     }
                 }
-                Ok(Value::Nil)
+                Ok(ControlFlow::Normal(Value::Nil))
+            }
+        }
+    }
+
+    // Executes stmts in the given environment, stopping early when a `break`
+    // or `return` needs to unwind to an enclosing loop or call. The previous
+    // environment is restored afterwards, even if evaluation failed.
+    pub(crate) fn execute_block(
+        &mut self,
+        stmts: &[Stmt],
+        env: Environment<Value>,
+    ) -> Result<ControlFlow, LoxError> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(env)));
+
+        let mut result = Ok(ControlFlow::Normal(Value::Nil));
+        for stmt in stmts {
+            let flow = self.evaluate_stmt(stmt);
+            if let Ok(ControlFlow::Break) | Ok(ControlFlow::Return(_)) | Err(_) = flow {
+                result = flow;
+                break;
+            }
+        }
+
+        self.environment = previous;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::{default_reserved, Scanner};
+
+    fn eval(source: &str) -> Result<Value, LoxError> {
+        let mut interpreter = Interpreter::new(Environment::new(None));
+        eval_with(&mut interpreter, source)
+    }
+
+    fn eval_with(interpreter: &mut Interpreter<Value>, source: &str) -> Result<Value, LoxError> {
+        let tokens = Scanner::new(default_reserved(), source)
+            .scan_tokens()
+            .unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        let locals = Resolver::new().resolve(&stmts).unwrap();
+        interpreter.resolve(locals);
+        interpreter.evaluate(&stmts)
+    }
+
+    fn assert_evaluates_to(source: &str, expected: Value) {
+        let value = eval(source).unwrap();
+        assert!(
+            value.equals(&expected),
+            "{} evaluated to {:?}, expected {:?}",
+            source,
+            value,
+            expected
+        );
+    }
+
+    fn assert_runtime_error(source: &str, message: &str) {
+        let err = eval(source).unwrap_err();
+        assert_eq!(err.kind, ErrorKind::RuntimeError, "{}", source);
+        assert_eq!(err.message, message, "{}", source);
+    }
+
+    #[test]
+    fn arithmetic_operators() {
+        assert_evaluates_to("1 + 2;", Value::Number(3.0));
+        assert_evaluates_to("5 - 7;", Value::Number(-2.0));
+        assert_evaluates_to("2 * 3.5;", Value::Number(7.0));
+        assert_evaluates_to("6 / 4;", Value::Number(1.5));
+        assert_evaluates_to("-123 * 45.67;", Value::Number(-123.0 * 45.67));
+    }
+
+    #[test]
+    fn arithmetic_operators_require_numbers() {
+        for op in &["-", "*", "/"] {
+            for (l, r) in &[
+                ("1", "\"a\""),
+                ("\"a\"", "1"),
+                ("nil", "1"),
+                ("true", "false"),
+            ] {
+                assert_runtime_error(&format!("{} {} {};", l, op, r), "Operands must be numbers.");
+            }
+        }
+    }
+
+    #[test]
+    fn plus_concatenates_strings() {
+        assert_evaluates_to("\"foo\" + \"bar\";", Value::String(String::from("foobar")));
+        assert_evaluates_to("\"\" + \"\";", Value::String(String::new()));
+    }
+
+    #[test]
+    fn plus_requires_two_numbers_or_two_strings() {
+        for (l, r) in &[
+            ("1", "\"a\""),
+            ("\"a\"", "1"),
+            ("nil", "nil"),
+            ("true", "1"),
+        ] {
+            assert_runtime_error(
+                &format!("{} + {};", l, r),
+                "Operands must be two numbers or two strings.",
+            );
+        }
+    }
+
+    #[test]
+    fn comparison_operators() {
+        assert_evaluates_to("1 < 2;", Value::True);
+        assert_evaluates_to("2 < 2;", Value::False);
+        assert_evaluates_to("2 <= 2;", Value::True);
+        assert_evaluates_to("3 <= 2;", Value::False);
+        assert_evaluates_to("2 > 1;", Value::True);
+        assert_evaluates_to("2 > 2;", Value::False);
+        assert_evaluates_to("2 >= 2;", Value::True);
+        assert_evaluates_to("1 >= 2;", Value::False);
+    }
+
+    #[test]
+    fn comparison_operators_require_numbers() {
+        for op in &["<", "<=", ">", ">="] {
+            for (l, r) in &[
+                ("1", "\"a\""),
+                ("\"a\"", "\"b\""),
+                ("nil", "3"),
+                ("1", "true"),
+            ] {
+                assert_runtime_error(&format!("{} {} {};", l, op, r), "Operands must be numbers.");
             }
         }
     }
+
+    #[test]
+    fn equality_operators() {
+        assert_evaluates_to("1 == 1;", Value::True);
+        assert_evaluates_to("1 == 2;", Value::False);
+        assert_evaluates_to("\"a\" == \"a\";", Value::True);
+        assert_evaluates_to("\"a\" == \"b\";", Value::False);
+        assert_evaluates_to("nil == nil;", Value::True);
+        assert_evaluates_to("true == true;", Value::True);
+        assert_evaluates_to("true == false;", Value::False);
+        assert_evaluates_to("nil == false;", Value::False);
+        assert_evaluates_to("1 == \"1\";", Value::False);
+        assert_evaluates_to("1 != 2;", Value::True);
+        assert_evaluates_to("nil != nil;", Value::False);
+        assert_evaluates_to("\"1\" != 1;", Value::True);
+    }
+
+    #[test]
+    fn unary_operators() {
+        assert_evaluates_to("-5;", Value::Number(-5.0));
+        assert_evaluates_to("--5;", Value::Number(5.0));
+        assert_evaluates_to("!true;", Value::False);
+        assert_evaluates_to("!false;", Value::True);
+        assert_evaluates_to("!nil;", Value::True);
+        assert_evaluates_to("!0;", Value::False);
+        assert_evaluates_to("!\"\";", Value::False);
+        assert_evaluates_to("!!nil;", Value::False);
+    }
+
+    #[test]
+    fn unary_minus_requires_a_number() {
+        for operand in &["\"a\"", "nil", "true"] {
+            assert_runtime_error(&format!("-{};", operand), "Operand must be a number.");
+        }
+    }
+
+    #[test]
+    fn clock_is_defined_by_default() {
+        match eval("clock();").unwrap() {
+            Value::Number(seconds) => assert!(seconds > 0.0),
+            value => panic!("clock() returned {:?}", value),
+        }
+    }
+
+    #[test]
+    fn natives_are_callable_from_scripts() {
+        fn double(args: &[Value]) -> Result<Value, LoxError> {
+            match args[0] {
+                Value::Number(n) => Ok(Value::Number(n * 2.0)),
+                _ => Err(LoxError::new(
+                    ErrorKind::RuntimeError,
+                    Span::default(),
+                    "double() takes a number.",
+                )),
+            }
+        }
+
+        let mut interpreter = Interpreter::new(Environment::new(None));
+        interpreter.define_native("double", 1, double);
+        let value = eval_with(&mut interpreter, "double(21);").unwrap();
+        assert!(value.equals(&Value::Number(42.0)));
+
+        let err = eval_with(&mut interpreter, "\n  double(nil);").unwrap_err();
+        assert_eq!(err.message, "double() takes a number.");
+        assert_eq!(err.span.line, 2);
+
+        let err = eval_with(&mut interpreter, "double(1, 2);").unwrap_err();
+        assert_eq!(err.message, "Expected 1 arguments but got 2.");
+    }
+
+    #[test]
+    fn deep_recursion_overflows_the_stack() {
+        assert_runtime_error("fun f() { f(); } f();", "Stack overflow.");
+        assert_runtime_error("class A { init() { A(); } } A();", "Stack overflow.");
+    }
+
+    #[test]
+    fn functions_keep_the_scope_depths_they_were_resolved_with() {
+        let mut interpreter = Interpreter::new(Environment::new(None));
+        eval_with(
+            &mut interpreter,
+            "fun make() { var a = 1; fun get() { return a; } return get; } var get = make();",
+        )
+        .unwrap();
+        eval_with(&mut interpreter, "{ var b = 2; b; }").unwrap();
+        assert_eq!(interpreter.locals.len(), 1);
+
+        let value = eval_with(&mut interpreter, "get();").unwrap();
+        assert!(value.equals(&Value::Number(1.0)));
+    }
+
+    #[test]
+    fn print_writes_to_the_configured_output() {
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let lines = Rc::clone(&printed);
+
+        let mut interpreter = Interpreter::new(Environment::new(None));
+        interpreter.set_output(Output::callback(move |line| {
+            lines.borrow_mut().push(line.to_string())
+        }));
+        eval_with(&mut interpreter, "print 1 + 2; print \"a\" + \"b\";").unwrap();
+
+        assert_eq!(*printed.borrow(), vec!["3", "ab"]);
+    }
 }
@@ -0,0 +1,89 @@
+//! RustyLox: a tree-walking interpreter for the Lox language from Bob
+//! Nystrom's _Crafting Interpreters_, with a bytecode compiler and `Vm` as an
+//! alternative backend.
+//!
+//! Each stage can be used on its own (`Scanner`, `Parser`, `Resolver`,
+//! `Interpreter`), or a whole program can be run with `Lox::eval_str`:
+//!
+//! ```
+//! use rustylox::{Lox, Value};
+//!
+//! let mut lox = Lox::new();
+//! lox.eval_str("var greeting = \"hello\";").unwrap();
+//! let value = lox.eval_str("greeting + \" world\";").unwrap();
+//! assert!(value.equals(&Value::String(String::from("hello world"))));
+//! ```
+
+#[macro_use]
+extern crate enum_display_derive;
+
+pub mod ast;
+pub mod class;
+pub mod diagnostic;
+pub mod environment;
+pub mod error;
+pub mod formatter;
+pub mod function;
+pub mod interpreter;
+pub mod output;
+pub mod parser;
+pub mod resolver;
+pub mod scanner;
+pub mod token;
+pub mod value;
+pub mod vm;
+
+pub use diagnostic::Renderer;
+pub use environment::Environment;
+pub use error::{ErrorKind, LoxError, Span};
+pub use formatter::format_source;
+pub use interpreter::Interpreter;
+pub use output::Output;
+pub use parser::Parser;
+pub use resolver::Resolver;
+pub use scanner::{default_reserved, Scanner};
+pub use value::Value;
+pub use vm::Vm;
+
+/// How deeply function calls can nest before a program fails with "Stack
+/// overflow.". Both backends start with this limit, which can be changed with
+/// `Interpreter::set_max_call_depth` or `Vm::set_max_call_depth`. The
+/// tree-walker recurses on the native stack for every call, so the default is
+/// kept low enough for a debug build on a default-sized thread.
+pub const MAX_CALL_DEPTH: usize = 64;
+
+/// A Lox session. Globals defined by one call to `eval_str` remain visible to
+/// later calls, the same way they do between lines in the REPL.
+pub struct Lox {
+    interpreter: Interpreter<Value>,
+}
+
+impl Lox {
+    pub fn new() -> Self {
+        Lox {
+            interpreter: Interpreter::new(Environment::new(None)),
+        }
+    }
+
+    /// The underlying interpreter, e.g. for installing native functions.
+    pub fn interpreter(&mut self) -> &mut Interpreter<Value> {
+        &mut self.interpreter
+    }
+
+    /// Scans, parses, resolves and evaluates `source`, returning the value of
+    /// the last statement. Evaluation stops at the first stage that reports
+    /// errors, and all of that stage's diagnostics are returned.
+    pub fn eval_str(&mut self, source: &str) -> Result<Value, Vec<LoxError>> {
+        let tokens = Scanner::new(default_reserved(), source).scan_tokens()?;
+        let stmts = Parser::new(tokens).parse()?;
+        let locals = Resolver::new().resolve(&stmts)?;
+        self.interpreter.resolve(locals);
+        self.interpreter.evaluate(&stmts).map_err(|err| vec![err])
+    }
+}
+
+impl Default for Lox {
+    fn default() -> Self {
+        Lox::new()
+    }
+}
@@ -1,104 +1,199 @@
-#[macro_use]
-extern crate enum_display_derive;
-
-use ast::{Expr, Stmt};
 use clap::{App, Arg, SubCommand};
-use environment::Environment;
-use error::{error, ErrorKind};
-use interpreter::Interpreter;
-use parser::Parser;
-use scanner::{default_reserved, Scanner};
+use repl::run_repl;
+use rustylox::ast::{Expr, Stmt};
+use rustylox::token::{Token, TokenType};
+use rustylox::vm::compiler::compile;
+use rustylox::vm::disassembler::disassemble;
+use rustylox::{
+    default_reserved, format_source, Environment, ErrorKind, Interpreter, LoxError, Parser,
+    Renderer, Resolver, Scanner, Span, Value, Vm,
+};
 use std::fs;
-use std::io::{stdin, stdout, Write};
-use std::path::Path;
-use token::{Token, TokenType};
-use value::Value;
-
-mod ast;
-mod environment;
-mod error;
-mod interpreter;
-mod parser;
-mod scanner;
-mod token;
-mod value;
-
-fn run_file(path: &Path) -> Result<(), ErrorKind> {
-    let env = Environment::<Value>::new(None);
-    let mut interpreter = Interpreter::new(env);
-    match fs::read_to_string(&path) {
-        Ok(source) => {
-            run(source, &mut interpreter);
-            Ok(())
+use std::io::{self, IsTerminal};
+
+mod completer;
+mod repl;
+
+/// Debug output requested on the command line, printed ahead of the
+/// program's own output.
+#[derive(Clone, Copy, Default)]
+struct Dump {
+    tokens: bool,
+    ast: bool,
+    result: bool,
+}
+
+/// The engine that runs a file: the tree-walking interpreter or the bytecode
+/// VM. The REPL always uses the tree-walker.
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    TreeWalker,
+    Vm,
+}
+
+fn report(file_name: &str, source: &str, diagnostics: &[LoxError]) {
+    let renderer = Renderer::new(file_name, source).with_color(io::stderr().is_terminal());
+    eprint!("{}", renderer.render_all(diagnostics));
+}
+
+// Reads a script, reporting a failure the same way as any other error.
+// Returns the process exit code on failure.
+fn read_source(file_name: &str) -> Result<String, i32> {
+    fs::read_to_string(file_name).map_err(|e| {
+        let err = LoxError::new(
+            ErrorKind::ReadFileError,
+            Span::default(),
+            format!("error reading file '{}'", file_name),
+        )
+        .with_note(e.to_string());
+        report(file_name, "", &[err]);
+        ErrorKind::ReadFileError.exit_code()
+    })
+}
+
+// Runs a script with the chosen backend. Returns the process exit code.
+fn run_file(file_name: &str, backend: Backend, trace: bool, dump: Dump) -> i32 {
+    let source = match read_source(file_name) {
+        Ok(source) => source,
+        Err(exit_code) => return exit_code,
+    };
+
+    let result = match backend {
+        Backend::TreeWalker => {
+            let env = Environment::<Value>::new(None);
+            run(source.clone(), &mut Interpreter::new(env), dump)
+        }
+        Backend::Vm => {
+            let mut vm = Vm::new();
+            if trace {
+                vm.trace_execution(io::stderr());
+            }
+            run_vm(&source, &mut vm, dump)
         }
-        Err(_) => {
-            error(
-                0,
-                0,
-                0,
-                "error reading file".to_string(),
-                ErrorKind::ReadFileError,
-            );
-            Err(ErrorKind::ReadFileError)
+    };
+    match result {
+        Ok(()) => 0,
+        Err(diagnostics) => {
+            report(file_name, &source, &diagnostics);
+            diagnostics[0].kind.exit_code()
         }
     }
 }
 
-fn run_repl() {
-    let stdin = stdin();
-    let mut stdout = stdout();
-    let env = Environment::<Value>::new(None);
-    let mut interpreter = &mut Interpreter::new(env);
-
-    loop {
-        print!("> ");
-        stdout.flush().unwrap();
-        let mut line = String::new();
-        match stdin.read_line(&mut line) {
-            Ok(bytes) => {
-                // Quit the repl if the user inputs an empty line or inputs 0 bytes as EOF (via ctrl-d).
-                if line == "\n" || bytes == 0 {
-                    break;
-                }
-                interpreter = run(line.to_string(), interpreter);
-            }
-            Err(e) => {
-                println!("{} error", e);
-            }
+// Prints the bytecode a file compiles to. Returns the process exit code.
+fn run_disasm(file_name: &str) -> i32 {
+    let source = match read_source(file_name) {
+        Ok(source) => source,
+        Err(exit_code) => return exit_code,
+    };
+
+    let compiled = parse(&source, Dump::default()).and_then(|stmts| {
+        Resolver::new().resolve(&stmts)?;
+        compile(&stmts)
+    });
+    match compiled {
+        Ok(function) => {
+            print!("{}", disassemble(&function));
+            0
+        }
+        Err(diagnostics) => {
+            report(file_name, &source, &diagnostics);
+            diagnostics[0].kind.exit_code()
         }
     }
 }
 
-fn run(source: String, interpreter: &mut Interpreter<Value>) -> &mut Interpreter<Value> {
-    let mut scanner = Scanner::new(default_reserved(), source.as_str());
-    match scanner.scan_tokens() {
-        Ok(tokens) => {
-            print!("Tokens:\n[");
-            for token in &tokens {
-                print!(" ({}) ", token);
+// Formats each file in place, or with `check` only reports the files that
+// aren't formatted. Returns the process exit code.
+fn run_fmt<'a>(paths: impl Iterator<Item = &'a str>, check: bool) -> i32 {
+    let mut exit_code = 0;
+    for file_name in paths {
+        let source = match read_source(file_name) {
+            Ok(source) => source,
+            Err(code) => {
+                exit_code = code;
+                continue;
+            }
+        };
+
+        match format_source(&source) {
+            Ok(formatted) if formatted == source => {}
+            Ok(_) if check => {
+                eprintln!("{} is not formatted", file_name);
+                exit_code = exit_code.max(1);
             }
-            print!("]\n\n");
-
-            let mut parser = Parser::new(tokens);
-            match parser.parse() {
-                Ok(stmts) => {
-                    println!("Parsed:");
-                    println!("{:?}\n", stmts);
-
-                    println!("Result:");
-                    match interpreter.evaluate(&stmts) {
-                        Ok(value) => {
-                            println!("{}", value);
-                        }
-                        _ => {}
-                    }
+            Ok(formatted) => {
+                if let Err(e) = fs::write(file_name, formatted) {
+                    eprintln!("error writing file '{}': {}", file_name, e);
+                    exit_code = ErrorKind::ReadFileError.exit_code();
                 }
-                _ => {}
+            }
+            Err(diagnostics) => {
+                report(file_name, &source, &diagnostics);
+                exit_code = exit_code.max(diagnostics[0].kind.exit_code());
             }
         }
-        Err(e) => eprintln!("{}", e),
     }
-    interpreter
+    exit_code
+}
+
+// Runs source through every stage, stopping at the first stage that reports
+// errors and handing all of that stage's diagnostics back to the caller.
+fn run(
+    source: String,
+    interpreter: &mut Interpreter<Value>,
+    dump: Dump,
+) -> Result<(), Vec<LoxError>> {
+    let stmts = parse(&source, dump)?;
+    let locals = Resolver::new().resolve(&stmts)?;
+    interpreter.resolve(locals);
+
+    let value = interpreter.evaluate(&stmts).map_err(|err| vec![err])?;
+    if dump.result {
+        println!("Result:");
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+// Like `run`, but compiles the program to bytecode and runs it on the VM.
+// Statements don't produce a value there, so `--dump-result` has no effect.
+fn run_vm(source: &str, vm: &mut Vm, dump: Dump) -> Result<(), Vec<LoxError>> {
+    let stmts = parse(source, dump)?;
+    Resolver::new().resolve(&stmts)?;
+    vm.interpret(&stmts)
+}
+
+// Scans and parses source, printing the tokens and statements if asked to.
+fn parse(source: &str, dump: Dump) -> Result<Vec<Stmt>, Vec<LoxError>> {
+    let mut scanner = Scanner::new(default_reserved(), source);
+    let tokens = scanner.scan_tokens()?;
+    if dump.tokens {
+        dump_tokens(&tokens);
+    }
+
+    let mut parser = Parser::new(tokens);
+    let stmts = parser.parse()?;
+    if dump.ast {
+        dump_stmts(&stmts);
+    }
+    Ok(stmts)
+}
+
+fn dump_tokens(tokens: &[Token]) {
+    print!("Tokens:\n[");
+    for token in tokens {
+        print!(" ({}) ", token);
+    }
+    print!("]\n\n");
+}
+
+fn dump_stmts(stmts: &[Stmt]) {
+    println!("Parsed:");
+    for stmt in stmts {
+        println!("{}", stmt);
+    }
+    println!();
 }
 
 fn run_ast() {
@@ -107,41 +202,143 @@ fn run_ast() {
     let expr = Stmt::ExprStmt(Expr::Binary(
         Box::new(Expr::Unary(
             Token::new(TokenType::Minus, String::from("-"), 0, 0, None),
-            Box::new(Expr::NumberLiteral(123 as f64)),
+            Box::new(Expr::NumberLiteral(
+                Token::new(TokenType::Number, String::from("123"), 0, 0, None),
+                123.0,
+            )),
         )),
         Token::new(TokenType::Star, String::from("*"), 0, 0, None),
-        Box::new(Expr::Grouping(Box::new(Expr::NumberLiteral(45.67)))),
+        Box::new(Expr::Grouping(Box::new(Expr::NumberLiteral(
+            Token::new(TokenType::Number, String::from("45.67"), 0, 0, None),
+            45.67,
+        )))),
     ));
-    println!("{:?}", expr);
+    println!("{}", expr);
     let mut interpreter = Interpreter::new(env);
-    println!("result: {:?}", interpreter.evaluate(&vec![expr]));
+    println!("result: {:?}", interpreter.evaluate(&[expr]));
 }
 
+// Deeply nested code recurses on the native stack in the parser, resolver
+// and tree-walker, so the CLI runs on a thread with room to spare.
+const STACK_SIZE: usize = 512 * 1024 * 1024;
+
 fn main() {
+    let cli = match std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run_cli)
+    {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("error: couldn't start the interpreter thread: {}", e);
+            // EX_OSERR from sysexits(3).
+            std::process::exit(71);
+        }
+    };
+    if let Err(panic) = cli.join() {
+        std::panic::resume_unwind(panic);
+    }
+}
+
+fn run_cli() {
     let m = App::new("rustylox")
         .version("0.0.1")
         .about("Rust interpreter for the Lox language")
         .subcommand(SubCommand::with_name("ast"))
         .subcommand(SubCommand::with_name("repl"))
+        .subcommand(
+            SubCommand::with_name("disasm")
+                .about("Prints the bytecode a Lox file compiles to")
+                .arg(Arg::with_name("file").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("fmt")
+                .about("Formats Lox source files in place")
+                .arg(
+                    Arg::with_name("check")
+                        .long("check")
+                        .help("Exits non-zero if any file isn't formatted, without changing it"),
+                )
+                .arg(Arg::with_name("files").required(true).multiple(true)),
+        )
         .arg(Arg::with_name("input").index(1))
+        .arg(
+            Arg::with_name("backend")
+                .long("backend")
+                .takes_value(true)
+                .possible_values(&["tree", "vm"])
+                .default_value("tree")
+                .help("Runs the file with the tree-walking interpreter or the bytecode VM"),
+        )
+        .arg(
+            Arg::with_name("trace-execution")
+                .long("trace-execution")
+                .help("Runs the file on the VM, printing the stack before each instruction"),
+        )
+        .arg(
+            Arg::with_name("dump-tokens")
+                .long("dump-tokens")
+                .global(true)
+                .help("Prints the scanned tokens before running"),
+        )
+        .arg(
+            Arg::with_name("dump-ast")
+                .long("dump-ast")
+                .global(true)
+                .help("Prints the parsed statements before running"),
+        )
+        .arg(
+            Arg::with_name("dump-result")
+                .long("dump-result")
+                .global(true)
+                .help("Prints the value of the last statement after running"),
+        )
         .get_matches();
 
-    if let Some(_) = m.subcommand_matches("ast") {
+    let dump = Dump {
+        tokens: m.is_present("dump-tokens"),
+        ast: m.is_present("dump-ast"),
+        result: m.is_present("dump-result"),
+    };
+
+    if m.subcommand_matches("ast").is_some() {
         return run_ast();
     }
 
-    if let Some(_) = m.subcommand_matches("repl") {
-        return run_repl();
+    if let Some(disasm) = m.subcommand_matches("disasm") {
+        std::process::exit(run_disasm(disasm.value_of("file").unwrap()));
+    }
+
+    if let Some(fmt) = m.subcommand_matches("fmt") {
+        let files = fmt.values_of("files").unwrap();
+        std::process::exit(run_fmt(files, fmt.is_present("check")));
+    }
+
+    if let Some(repl) = m.subcommand_matches("repl") {
+        return run_repl(Dump {
+            tokens: dump.tokens || repl.is_present("dump-tokens"),
+            ast: dump.ast || repl.is_present("dump-ast"),
+            result: dump.result || repl.is_present("dump-result"),
+        });
     }
 
     if m.is_present("input") {
         if let Some(file_path) = m.value_of("input") {
-            std::process::exit(match run_file(Path::new(file_path)) {
-                Ok(_) => 0,
-                Err(_) => 65,
-            });
+            let trace = m.is_present("trace-execution");
+            // Tracing implies the VM unless another backend was asked for.
+            let explicit_backend = m.occurrences_of("backend") > 0;
+            let backend = match m.value_of("backend") {
+                Some("vm") => Backend::Vm,
+                _ if trace && explicit_backend => clap::Error::with_description(
+                    "--trace-execution only works with --backend=vm",
+                    clap::ErrorKind::ArgumentConflict,
+                )
+                .exit(),
+                _ if trace => Backend::Vm,
+                _ => Backend::TreeWalker,
+            };
+            std::process::exit(run_file(file_path, backend, trace, dump));
         }
     }
 
-    run_repl();
+    run_repl(dump);
 }
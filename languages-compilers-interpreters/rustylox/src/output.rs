@@ -0,0 +1,56 @@
+use super::error::{ErrorKind, LoxError, Span};
+use std::fmt;
+use std::io::{self, Write};
+
+/// Where `print` statements send their text. Defaults to stdout; hosts can
+/// substitute any writer (a file, a `Vec<u8>`, `io::sink()`) or a callback
+/// that receives each printed line without its trailing newline.
+pub enum Output {
+    Writer(Box<dyn Write>),
+    Callback(Box<dyn FnMut(&str)>),
+}
+
+impl Output {
+    /// Sends printed text to `writer`.
+    pub fn writer<W: Write + 'static>(writer: W) -> Self {
+        Output::Writer(Box::new(writer))
+    }
+
+    /// Passes each printed line to `callback`.
+    pub fn callback<F: FnMut(&str) + 'static>(callback: F) -> Self {
+        Output::Callback(Box::new(callback))
+    }
+
+    /// Writes `line` followed by a newline. A failed write is a runtime error.
+    pub fn print(&mut self, line: &str) -> Result<(), LoxError> {
+        let written = match self {
+            Output::Writer(writer) => writeln!(writer, "{}", line),
+            Output::Callback(callback) => {
+                callback(line);
+                Ok(())
+            }
+        };
+        written.map_err(|err| {
+            LoxError::new(
+                ErrorKind::RuntimeError,
+                Span::default(),
+                format!("Failed to write output: {}", err),
+            )
+        })
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::Writer(Box::new(io::stdout()))
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Output::Writer(_) => write!(f, "Output::Writer"),
+            Output::Callback(_) => write!(f, "Output::Callback"),
+        }
+    }
+}
@@ -1,44 +1,58 @@
-use super::ast::{Expr, Stmt};
-use super::error::{error, ErrorKind};
+use super::ast::{next_expr_id, Expr, Stmt};
+use super::error::{ErrorKind, LoxError};
 use super::token::{Literal, Token, TokenType};
 
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
     loop_depth: usize,
+    function_depth: usize,
+    errors: Vec<LoxError>,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Parser {
         Parser {
-            tokens: tokens,
+            tokens,
             current: 0,
             loop_depth: 0,
+            function_depth: 0,
+            errors: Vec::new(),
         }
     }
 
     /*
     program        -> statement* EOF ;
 
-    declaration    -> varDecl
+    declaration    -> classDecl
+                    | funDecl
+                    | varDecl
                     | statement ;
 
     statement      -> exprStmt
                     | printStmt
+                    | returnStmt
                     | block
                     | ifStmt
                     | whileStmt ;
 
     exprStmt       -> expression ";" ;
     printStmt      -> "print" expression ";" ;
+    returnStmt     -> "return" expression? ";" ;
     block          -> "{" declaration* "}" ;
     ifStmt         -> "if" "(" expression ")" statement
                     ( "else" statement )? ;
     whileStmt      -> "while" "(" expression ")" statement | break;
 
+    classDecl      -> "class" identifier ( "<" identifier )?
+                      "{" function* "}" ;
+    funDecl        -> "fun" function ;
+    function       -> identifier "(" parameters? ")" block ;
+    parameters     -> identifier ( "," identifier )* ;
+
     varDecl        -> "var" identifier ( "=" expression )? ";" ;
     expression     -> assignment ;
-    assignment     → identifier "=" assignment
+    assignment     → ( call "." )? identifier "=" assignment
                     | logic_or ;
     logic_or       -> logic_and ( "or" logic_and )* ;
     logic_and      -> equality ( "and" equality )* ;
@@ -50,36 +64,122 @@ impl Parser {
     factor         → unary ( ( "/" | "*" ) unary )* ;
     unary          → ( "!" | "-" ) unary | call ;
 
-    call           -> primary ( "(" arguments? ")" )* ;
+    call           -> primary ( "(" arguments? ")" | "." identifier )* ;
     arguments      -> expression ( "," expression )* ;
 
-    primary        → NUMBER | STRING | "true" | "false" | "nil"
-                    | "(" expression ")"
+    primary        → NUMBER | STRING | "true" | "false" | "nil" | "this"
+                    | "(" expression ")" | "super" "." identifier
                     | identifier ;
     */
-    pub fn parse(&mut self) -> Result<Vec<Stmt>, ()> {
+    pub fn parse(&mut self) -> Result<Vec<Stmt>, Vec<LoxError>> {
         let mut stmts = Vec::new();
         while !self.at_end() {
-            stmts.push(self.declaration()?);
+            // A declaration that fails has already recorded its error and
+            // skipped ahead to the next statement, so parsing carries on to
+            // report any further errors. Nothing is returned to run unless
+            // the whole program parsed.
+            if let Ok(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
+        }
+
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
         }
         Ok(stmts)
     }
 
+    /// Parses the tokens as a single expression with nothing after it, such
+    /// as a bare `1 + 2` typed at the REPL.
+    pub fn parse_expression(&mut self) -> Result<Expr, Vec<LoxError>> {
+        let expr = self.expression();
+        if expr.is_ok() && !self.at_end() {
+            self.error(self.peek(), "Expect end of expression.");
+        }
+
+        match expr {
+            Ok(expr) if self.errors.is_empty() => Ok(expr),
+            _ => Err(self.errors.clone()),
+        }
+    }
+
+    // Panic-mode recovery happens here: when anything inside a declaration
+    // fails, skip to the start of the next statement and let the caller keep
+    // going.
     fn declaration(&mut self) -> Result<Stmt, ()> {
-        if self.match_token(vec![TokenType::Var]) {
-            if let Ok(var_decl) = self.var_declaration() {
-                return Ok(var_decl);
+        let stmt = if self.match_token(vec![TokenType::Class]) {
+            self.class_declaration()
+        } else if self.match_token(vec![TokenType::Fun]) {
+            self.function("function")
+        } else if self.match_token(vec![TokenType::Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        };
+
+        if stmt.is_err() {
+            self.synchronize();
+        }
+        stmt
+    }
+
+    fn class_declaration(&mut self) -> Result<Stmt, ()> {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let mut superclass = None;
+        if self.match_token(vec![TokenType::Less]) {
+            let superclass_name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+            superclass = Some(Expr::Variable(next_expr_id(), superclass_name));
+        }
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(name, superclass, methods))
+    }
+
+    fn function(&mut self, kind: &str) -> Result<Stmt, ()> {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= 255 {
+                    self.error(self.peek(), "Can't have more than 255 parameters.");
+                }
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+                if !self.match_token(vec![TokenType::Comma]) {
+                    break;
+                }
             }
         }
-        self.statement().or_else(|_| {
-            self.synchronize();
-            Err(())
-        })
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        // A `break` inside the body can't refer to a loop surrounding the declaration.
+        let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.block_stmts();
+        self.function_depth -= 1;
+        self.loop_depth = loop_depth;
+
+        Ok(Stmt::Function(name, params, body?))
     }
 
     fn var_declaration(&mut self) -> Result<Stmt, ()> {
-        self.consume(TokenType::Identifier, "Expect variable name.");
-        let name = self.previous();
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
 
         let mut initializer = None;
         if self.match_token(vec![TokenType::Equal]) {
@@ -89,7 +189,7 @@ impl Parser {
         self.consume(
             TokenType::Semicolon,
             "Expect ';' after variable declaration.",
-        );
+        )?;
 
         Ok(Stmt::VarDeclaration(name, initializer))
     }
@@ -97,15 +197,12 @@ impl Parser {
     fn statement(&mut self) -> Result<Stmt, ()> {
         if self.match_token(vec![TokenType::Break]) {
             if self.loop_depth < 1 {
-                error(
-                    self.previous().line,
-                    self.previous().column,
-                    self.previous().column,
-                    String::from("Must be inside loop to use 'break' statement."),
-                    ErrorKind::ParseError,
+                self.error(
+                    self.previous(),
+                    "Must be inside loop to use 'break' statement.",
                 );
             }
-            self.consume(TokenType::Semicolon, "Expect ';' after 'break'.");
+            self.consume(TokenType::Semicolon, "Expect ';' after 'break'.")?;
             return Ok(Stmt::Break);
         }
 
@@ -121,6 +218,10 @@ impl Parser {
             return self.print_stmt();
         }
 
+        if self.match_token(vec![TokenType::Return]) {
+            return self.return_stmt();
+        }
+
         if self.match_token(vec![TokenType::While]) {
             return self.while_stmt();
         }
@@ -133,7 +234,7 @@ impl Parser {
     }
 
     fn for_stmt(&mut self) -> Result<Stmt, ()> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
         let initializer: Option<Stmt>;
         if self.match_token(vec![TokenType::Semicolon]) {
@@ -148,26 +249,28 @@ impl Parser {
         if !self.check(TokenType::Semicolon) {
             condition = Some(self.expression()?);
         }
-        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.");
+        let semicolon = self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
 
         let mut increment = None;
         if !self.check(TokenType::RightParen) {
             increment = Some(self.expression()?);
         }
-        self.consume(TokenType::RightParen, "Expect ')' after loop increment.");
+        self.consume(TokenType::RightParen, "Expect ')' after loop increment.")?;
 
         self.loop_depth += 1;
-        let mut body = self.statement()?;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        let mut body = body?;
         if let Some(increment) = increment {
             body = Stmt::Block(vec![body, Stmt::ExprStmt(increment)]);
         }
 
         body = match condition {
-            None => Stmt::While(Expr::TrueLiteral, Box::new(body)),
+            // A missing condition is always true, placed where it would be.
+            None => Stmt::While(Expr::TrueLiteral(semicolon), Box::new(body)),
             Some(expr) => Stmt::While(expr, Box::new(body)),
         };
 
-        self.loop_depth -= 1;
         match initializer {
             None => Ok(body),
             Some(expr) => Ok(Stmt::Block(vec![expr, body])),
@@ -175,9 +278,9 @@ impl Parser {
     }
 
     fn if_stmt(&mut self) -> Result<Stmt, ()> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.");
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
         let condition = self.expression()?;
-        self.consume(TokenType::RightParen, "Expect ')' after condition.");
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
 
         let then_branch = Box::new(self.statement()?);
         let mut else_branch = None;
@@ -189,36 +292,57 @@ impl Parser {
     }
 
     fn while_stmt(&mut self) -> Result<Stmt, ()> {
-        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
-        self.consume(TokenType::RightParen, "Expect ')' after condition.");
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
         self.loop_depth += 1;
-        let body = Box::new(self.statement()?);
+        let body = self.statement();
         self.loop_depth -= 1;
-        Ok(Stmt::While(condition, body))
+        Ok(Stmt::While(condition, Box::new(body?)))
     }
 
     fn block(&mut self) -> Result<Stmt, ()> {
+        Ok(Stmt::Block(self.block_stmts()?))
+    }
+
+    fn block_stmts(&mut self) -> Result<Vec<Stmt>, ()> {
         let mut stmts = Vec::new();
 
-        while !self.check(TokenType::RightBrace) {
-            stmts.push(self.declaration()?);
+        while !self.check(TokenType::RightBrace) && !self.at_end() {
+            if let Ok(stmt) = self.declaration() {
+                stmts.push(stmt);
+            }
         }
 
-        self.consume(TokenType::RightBrace, "Expect '}' after block.");
-        Ok(Stmt::Block(stmts))
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(stmts)
     }
 
     fn print_stmt(&mut self) -> Result<Stmt, ()> {
-        let value = self.expression();
-        self.consume(TokenType::Semicolon, "Expect ';' after value.");
-        Ok(Stmt::Print(value?))
+        let value = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print(value))
+    }
+
+    fn return_stmt(&mut self) -> Result<Stmt, ()> {
+        let keyword = self.previous();
+        if self.function_depth < 1 {
+            self.error(keyword.clone(), "Can't return from top-level code.");
+        }
+
+        let mut value = None;
+        if !self.check(TokenType::Semicolon) {
+            value = Some(self.expression()?);
+        }
+
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(keyword, value))
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ()> {
-        let expr = self.expression();
-        self.consume(TokenType::Semicolon, "Expect ';' after expression.");
-        Ok(Stmt::ExprStmt(expr?))
+        let expr = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::ExprStmt(expr))
     }
 
     fn expression(&mut self) -> Result<Expr, ()> {
@@ -257,32 +381,33 @@ impl Parser {
             let value = self.assignment()?;
 
             match expr {
-                Expr::Variable(name) => {
-                    return Ok(Expr::Assignment(name, Box::new(value)));
+                Expr::Variable(_, name) => {
+                    return Ok(Expr::Assignment(next_expr_id(), name, Box::new(value)));
+                }
+                Expr::Get(object, name) => {
+                    return Ok(Expr::Set(object, name, Box::new(value)));
                 }
-                _ => self.error(equals, "Invalid assignment target"),
+                _ => self.error(equals, "Invalid assignment target."),
             }
         }
 
         Ok(expr)
     }
 
-    }
-
     fn equality(&mut self) -> Result<Expr, ()> {
-        let mut expr = self.comparison();
+        let mut expr = self.comparison()?;
 
         while self.match_token(vec![TokenType::BangEqual, TokenType::EqualEqual]) {
             let operator = self.previous();
-            let right = self.comparison();
-            expr = Ok(Expr::Binary(Box::new(expr?), operator, Box::new(right?)));
+            let right = self.comparison()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
 
-        expr
+        Ok(expr)
     }
 
     fn comparison(&mut self) -> Result<Expr, ()> {
-        let mut expr = self.term();
+        let mut expr = self.term()?;
 
         while self.match_token(vec![
             TokenType::Greater,
@@ -291,75 +416,72 @@ impl Parser {
             TokenType::LessEqual,
         ]) {
             let operator = self.previous();
-            let right = self.term();
-            expr = Ok(Expr::Binary(Box::new(expr?), operator, Box::new(right?)));
+            let right = self.term()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
 
-        expr
+        Ok(expr)
     }
 
     fn term(&mut self) -> Result<Expr, ()> {
-        let mut expr = self.factor();
+        let mut expr = self.factor()?;
 
         while self.match_token(vec![TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous();
-            let right = self.factor();
-            expr = Ok(Expr::Binary(Box::new(expr?), operator, Box::new(right?)));
+            let right = self.factor()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
 
-        expr
+        Ok(expr)
     }
 
     fn factor(&mut self) -> Result<Expr, ()> {
-        let mut expr = self.unary();
+        let mut expr = self.unary()?;
 
         while self.match_token(vec![TokenType::Slash, TokenType::Star]) {
             let operator = self.previous();
-            let right = self.unary();
-            expr = Ok(Expr::Binary(Box::new(expr?), operator, Box::new(right?)));
+            let right = self.unary()?;
+            expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
         }
 
-        expr
+        Ok(expr)
     }
 
     fn unary(&mut self) -> Result<Expr, ()> {
         if self.match_token(vec![TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous();
-            let right = self.unary();
-            return Ok(Expr::Unary(operator, Box::new(right?)));
+            let right = self.unary()?;
+            return Ok(Expr::Unary(operator, Box::new(right)));
         }
 
         self.call()
     }
 
     fn call(&mut self) -> Result<Expr, ()> {
-        let mut expr = self.primary();
+        let mut expr = self.primary()?;
 
         loop {
             if self.match_token(vec![TokenType::LeftParen]) {
-                expr = self.finish_call(&expr?);
+                expr = self.finish_call(&expr)?;
+            } else if self.match_token(vec![TokenType::Dot]) {
+                let name =
+                    self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::Get(Box::new(expr), name);
             } else {
                 break;
             }
         }
 
-        expr
+        Ok(expr)
     }
 
     fn finish_call(&mut self, callee: &Expr) -> Result<Expr, ()> {
         let mut args = Vec::new();
 
-        if !self.match_token(vec![TokenType::RightParen]) {
+        if !self.check(TokenType::RightParen) {
             loop {
-                if args.len() > 255 {
-                    let next = self.peek();
-                    error(
-                        next.line,
-                        next.column,
-                        next.column,
-                        String::from("Can't have more than 255 arguments."),
-                        ErrorKind::ParseError,
-                    );
+                if args.len() >= 255 {
+                    self.error(self.peek(), "Can't have more than 255 arguments.");
                 }
                 args.push(self.expression()?);
                 if !self.match_token(vec![TokenType::Comma]) {
@@ -368,16 +490,15 @@ impl Parser {
             }
         }
 
-        self.consume(TokenType::RightParen, "Expect ')' after arguments.");
-        let paren = self.previous();
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
 
-        return Ok(Expr::Call(Box::new(callee.clone()), paren, args));
+        Ok(Expr::Call(Box::new(callee.clone()), paren, args))
     }
 
     fn primary(&mut self) -> Result<Expr, ()> {
         if self.match_token(vec![TokenType::False]) {
             if let Some(Literal::False) = self.previous().literal {
-                return Ok(Expr::FalseLiteral);
+                return Ok(Expr::FalseLiteral(self.previous()));
             } else {
                 self.error(self.previous(), "Expected boolean");
                 return Err(());
@@ -386,7 +507,7 @@ impl Parser {
 
         if self.match_token(vec![TokenType::True]) {
             if let Some(Literal::True) = self.previous().literal {
-                return Ok(Expr::TrueLiteral);
+                return Ok(Expr::TrueLiteral(self.previous()));
             } else {
                 self.error(self.previous(), "Expected boolean");
                 return Err(());
@@ -394,12 +515,13 @@ impl Parser {
         }
 
         if self.match_token(vec![TokenType::Nil]) {
-            return Ok(Expr::NilLiteral);
+            return Ok(Expr::NilLiteral(self.previous()));
         }
 
         if self.match_token(vec![TokenType::Number]) {
-            if let Some(Literal::Number(n)) = self.previous().literal {
-                return Ok(Expr::NumberLiteral(n));
+            let token = self.previous();
+            if let Some(Literal::Number(n)) = token.literal {
+                return Ok(Expr::NumberLiteral(token, n));
             } else {
                 self.error(self.previous(), "Expected number");
                 return Err(());
@@ -407,25 +529,37 @@ impl Parser {
         }
 
         if self.match_token(vec![TokenType::String]) {
-            if let Some(Literal::String(s)) = self.previous().literal {
-                return Ok(Expr::StringLiteral(s));
+            let token = self.previous();
+            if let Some(Literal::String(s)) = token.literal.clone() {
+                return Ok(Expr::StringLiteral(token, s));
             } else {
                 self.error(self.previous(), "Expected string");
                 return Err(());
             }
         }
 
+        if self.match_token(vec![TokenType::Super]) {
+            let keyword = self.previous();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self.consume(TokenType::Identifier, "Expect superclass method name.")?;
+            return Ok(Expr::Super(next_expr_id(), keyword, method));
+        }
+
+        if self.match_token(vec![TokenType::This]) {
+            return Ok(Expr::This(next_expr_id(), self.previous()));
+        }
+
         if self.match_token(vec![TokenType::Identifier]) {
-            return Ok(Expr::Variable(self.previous()));
+            return Ok(Expr::Variable(next_expr_id(), self.previous()));
         }
 
         if self.match_token(vec![TokenType::LeftParen]) {
-            let expr = self.expression();
-            self.consume(TokenType::RightParen, "Expect ')' to close expression");
-            return Ok(Expr::Grouping(Box::new(expr?)));
+            let expr = self.expression()?;
+            self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
+            return Ok(Expr::Grouping(Box::new(expr)));
         }
 
-        self.error(self.peek(), "Expect expression");
+        self.error(self.peek(), "Expect expression.");
         Err(())
     }
 
@@ -467,26 +601,26 @@ impl Parser {
         false
     }
 
-    fn consume(&mut self, token_type: TokenType, message: &str) {
+    // Advances past the expected token and returns it, or records a syntax
+    // error and fails so that `declaration` can resynchronize.
+    fn consume(&mut self, token_type: TokenType, message: &str) -> Result<Token, ()> {
         if self.check(token_type) {
-            return self.advance();
+            self.advance();
+            return Ok(self.previous());
         }
 
-        self.error(self.peek(), message)
+        self.error(self.peek(), message);
+        Err(())
     }
 
-    fn error(&self, token: Token, message: &str) {
-        let mut msg = message.to_string();
+    // Records a syntax error. Callers return `Err(())` afterwards when the
+    // parser can't sensibly continue with the current declaration.
+    fn error(&mut self, token: Token, message: &str) {
+        let mut err = LoxError::at_token(ErrorKind::ParseError, &token, message);
         if token.token_type == TokenType::EOF {
-            msg += " at end of input";
-        }
-        error(
-            token.line,
-            token.column,
-            token.column,
-            message.to_string(),
-            ErrorKind::ParseError,
-        );
+            err = err.with_note("at end of input");
+        }
+        self.errors.push(err);
     }
 
     fn synchronize(&mut self) {
@@ -0,0 +1,335 @@
+use super::completer::LoxHelper;
+use super::{dump_stmts, dump_tokens, report, run, Dump};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Config, Editor};
+use rustylox::ast::{Expr, Stmt};
+use rustylox::token::TokenType;
+use rustylox::{
+    default_reserved, Environment, Interpreter, LoxError, Parser, Resolver, Scanner, Value,
+};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+const PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+const HISTORY_FILE: &str = ".rustylox_history";
+
+const HELP: &str = "\
+Enter Lox statements, or an expression without a `;` to see its value.
+
+  :env           show the variables that are defined
+  :load <file>   run a file in this session
+  :reset         forget every definition
+  :ast <code>    show how code parses
+  :tokens <code> show how code scans
+  :time <code>   run code and show how long it took
+  :help          show this message";
+
+/// Reads programs from the terminal with line editing, history and tab
+/// completion until Ctrl-D. Input that stops mid-statement, such as an open block, keeps
+/// reading with a `... ` prompt, and Ctrl-C throws away whatever has been
+/// typed so far.
+pub fn run_repl(dump: Dump) {
+    let config = Config::builder()
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = match Editor::<LoxHelper, DefaultHistory>::with_config(config) {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("error starting the repl: {}", e);
+            return;
+        }
+    };
+    let history = history_path();
+    if let Some(path) = &history {
+        // There's no history yet the first time the repl runs.
+        let _ = editor.load_history(path);
+    }
+
+    let mut repl = Repl::new(dump);
+    let mut input = String::new();
+    loop {
+        // `:reset` replaces the environment, so completion follows the
+        // current one.
+        editor.set_helper(Some(LoxHelper::new(Rc::clone(
+            &repl.interpreter.environment,
+        ))));
+
+        let prompt = if input.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        match editor.readline(prompt) {
+            Ok(line) => {
+                if input.is_empty() && line.trim_start().starts_with(':') {
+                    let _ = editor.add_history_entry(line.trim());
+                    repl.command(line.trim());
+                    continue;
+                }
+
+                input += &line;
+                input.push('\n');
+                if bare_expression(&input).is_none() && is_incomplete(&input) {
+                    continue;
+                }
+                if !input.trim().is_empty() {
+                    let _ = editor.add_history_entry(input.trim_end());
+                    repl.eval(&input);
+                }
+                input.clear();
+            }
+            Err(ReadlineError::Interrupted) => input.clear(),
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("error reading input: {}", e);
+                break;
+            }
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("error saving history to '{}': {}", path.display(), e);
+        }
+    }
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+struct Repl {
+    interpreter: Interpreter<Value>,
+    dump: Dump,
+}
+
+impl Repl {
+    fn new(dump: Dump) -> Self {
+        Repl {
+            interpreter: Interpreter::new(Environment::new(None)),
+            dump,
+        }
+    }
+
+    // Runs a complete input. A bare expression has its value printed.
+    fn eval(&mut self, source: &str) {
+        let result = match bare_expression(source) {
+            Some(expr) => {
+                self.dump_expression(source, &expr);
+                self.eval_expression(expr)
+                    .map(|value| println!("{}", value))
+            }
+            None => run(source.to_string(), &mut self.interpreter, self.dump),
+        };
+        if let Err(diagnostics) = result {
+            report("<repl>", source, &diagnostics);
+        }
+    }
+
+    // Applies `--dump-tokens` and `--dump-ast` to a bare expression, which
+    // doesn't go through `run`.
+    fn dump_expression(&self, source: &str, expr: &Expr) {
+        if self.dump.tokens {
+            if let Ok(tokens) = Scanner::new(default_reserved(), source).scan_tokens() {
+                dump_tokens(&tokens);
+            }
+        }
+        if self.dump.ast {
+            dump_stmts(&[Stmt::ExprStmt(expr.clone())]);
+        }
+    }
+
+    fn eval_expression(&mut self, expr: Expr) -> Result<Value, Vec<LoxError>> {
+        let stmts = [Stmt::ExprStmt(expr)];
+        let locals = Resolver::new().resolve(&stmts)?;
+        self.interpreter.resolve(locals);
+        self.interpreter.evaluate(&stmts).map_err(|err| vec![err])
+    }
+
+    fn command(&mut self, line: &str) {
+        let (name, arg) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+
+        match name {
+            ":env" => self.print_env(),
+            ":load" if !arg.is_empty() => self.load(arg),
+            ":reset" => {
+                self.interpreter = Interpreter::new(Environment::new(None));
+                println!("All definitions cleared.");
+            }
+            ":ast" if !arg.is_empty() => print_ast(arg),
+            ":tokens" if !arg.is_empty() => print_tokens(arg),
+            ":time" if !arg.is_empty() => {
+                let start = Instant::now();
+                self.eval(arg);
+                println!("Took {:?}.", start.elapsed());
+            }
+            ":help" => println!("{}", HELP),
+            ":load" | ":ast" | ":tokens" | ":time" => {
+                eprintln!("{} needs an argument. Type :help for usage.", name)
+            }
+            _ => eprintln!("Unknown command '{}'. Type :help for a list.", name),
+        }
+    }
+
+    // Prints every binding visible from the current scope, innermost first,
+    // leaving out those shadowed by an inner scope.
+    fn print_env(&self) {
+        let mut seen = HashSet::new();
+        let mut env = Some(Rc::clone(&self.interpreter.environment));
+        while let Some(scope) = env {
+            let scope = scope.borrow();
+            let mut names: Vec<&String> = scope.values.keys().collect();
+            names.sort();
+            for name in names {
+                if seen.insert(name.clone()) {
+                    println!("{} = {}", name, scope.values[name]);
+                }
+            }
+            env = scope.enclosing.clone();
+        }
+    }
+
+    fn load(&mut self, path: &str) {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                if let Err(diagnostics) = run(source.clone(), &mut self.interpreter, self.dump) {
+                    report(path, &source, &diagnostics);
+                }
+            }
+            Err(e) => eprintln!("error reading file '{}': {}", path, e),
+        }
+    }
+}
+
+fn print_ast(source: &str) {
+    if let Some(expr) = bare_expression(source) {
+        println!("{}", expr);
+        return;
+    }
+
+    let parsed = Scanner::new(default_reserved(), source)
+        .scan_tokens()
+        .and_then(|tokens| Parser::new(tokens).parse());
+    match parsed {
+        Ok(stmts) => stmts.iter().for_each(|stmt| println!("{}", stmt)),
+        Err(diagnostics) => report("<repl>", source, &diagnostics),
+    }
+}
+
+fn print_tokens(source: &str) {
+    match Scanner::new(default_reserved(), source).scan_tokens() {
+        Ok(tokens) => {
+            for token in tokens {
+                println!("{}:{} {}", token.line, token.column, token);
+            }
+        }
+        Err(diagnostics) => report("<repl>", source, &diagnostics),
+    }
+}
+
+// The input parsed as a single expression, when that's all it is.
+fn bare_expression(source: &str) -> Option<Expr> {
+    let tokens = Scanner::new(default_reserved(), source)
+        .scan_tokens()
+        .ok()?;
+    Parser::new(tokens).parse_expression().ok()
+}
+
+// Whether more lines are needed before `source` can be run: a string, block
+// comment, brace or paren is still open, or the parser ran out of input in
+// the middle of a statement. Any other error is reported straight away.
+fn is_incomplete(source: &str) -> bool {
+    let tokens = match Scanner::new(default_reserved(), source).scan_tokens() {
+        Ok(tokens) => tokens,
+        Err(errors) => {
+            return errors.iter().any(|err| {
+                err.message == "Unterminated string."
+                    || err.message == "Unterminated block comment."
+            })
+        }
+    };
+
+    let mut depth = 0;
+    for token in &tokens {
+        match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBrace => depth += 1,
+            TokenType::RightParen | TokenType::RightBrace => depth -= 1,
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+
+    match Parser::new(tokens).parse() {
+        Ok(_) => false,
+        Err(errors) => errors
+            .iter()
+            .any(|err| err.notes.iter().any(|note| note == "at end of input")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complete_statements_run_straight_away() {
+        assert!(!is_incomplete("print 1;\n"));
+        assert!(!is_incomplete("{ var a = 1; }\n"));
+        assert!(!is_incomplete("\n"));
+    }
+
+    #[test]
+    fn open_blocks_and_parens_need_more_input() {
+        assert!(is_incomplete("while (true) {\n"));
+        assert!(is_incomplete("print (1 +\n"));
+        assert!(is_incomplete("fun f() {\n  if (a) {\n  }\n"));
+    }
+
+    #[test]
+    fn statements_cut_off_at_the_end_need_more_input() {
+        assert!(is_incomplete("var a = 1\n"));
+        assert!(is_incomplete("if (a)\n"));
+        assert!(is_incomplete("print \"two\nlines\n"));
+        assert!(is_incomplete("/* still\n"));
+    }
+
+    #[test]
+    fn other_errors_are_reported_straight_away() {
+        assert!(!is_incomplete("print 1 +;\n"));
+        assert!(!is_incomplete("}\n"));
+        assert!(!is_incomplete("print @;\n"));
+    }
+
+    #[test]
+    fn recognizes_bare_expressions() {
+        assert_eq!(bare_expression("1 + 2\n").unwrap().to_string(), "(+ 1 2)");
+        assert_eq!(
+            bare_expression("a = f(b)").unwrap().to_string(),
+            "(= a (call f b))"
+        );
+        assert!(bare_expression("1 + 2;").is_none());
+        assert!(bare_expression("print 1").is_none());
+        assert!(bare_expression("1 2").is_none());
+        assert!(bare_expression("").is_none());
+    }
+
+    #[test]
+    fn bare_expressions_see_earlier_definitions() {
+        let mut repl = Repl::new(Dump::default());
+        repl.eval("var a = 20;\n");
+        let value = repl
+            .eval_expression(bare_expression("a * 2 + 2").unwrap())
+            .unwrap();
+        assert!(value.equals(&Value::Number(42.0)));
+    }
+}
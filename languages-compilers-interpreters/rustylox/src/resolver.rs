@@ -0,0 +1,255 @@
+use super::ast::{Expr, ExprId, Stmt};
+use super::error::{ErrorKind, LoxError};
+use super::token::Token;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum FunctionType {
+    #[default]
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, Default, PartialEq)]
+enum ClassType {
+    #[default]
+    None,
+    Class,
+    Subclass,
+}
+
+/// Walks the parsed program once before it is interpreted, recording for each
+/// local variable reference how many scopes separate it from its declaration.
+/// References that are never resolved are assumed to be globals.
+#[derive(Default)]
+pub struct Resolver {
+    // Each scope maps a name to whether its initializer has finished resolving.
+    scopes: Vec<HashMap<String, bool>>,
+    locals: HashMap<ExprId, usize>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: Vec<LoxError>,
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn resolve(mut self, stmts: &[Stmt]) -> Result<HashMap<ExprId, usize>, Vec<LoxError>> {
+        self.resolve_stmts(stmts);
+        if !self.errors.is_empty() {
+            return Err(self.errors);
+        }
+        Ok(self.locals)
+    }
+
+    fn resolve_stmts(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.resolve_stmt(stmt);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Break => {}
+            Stmt::Class(name, superclass, methods) => {
+                let enclosing_class = self.current_class;
+                self.current_class = ClassType::Class;
+                self.declare(name);
+                self.define(name);
+
+                if let Some(superclass) = superclass {
+                    if let Expr::Variable(_, superclass_name) = superclass {
+                        if superclass_name.lexeme == name.lexeme {
+                            self.error(superclass_name, "A class can't inherit from itself.");
+                        }
+                    }
+                    self.current_class = ClassType::Subclass;
+                    self.resolve_expr(superclass);
+
+                    // Methods close over an extra scope that binds `super`.
+                    self.begin_scope();
+                    if let Some(scope) = self.scopes.last_mut() {
+                        scope.insert(String::from("super"), true);
+                    }
+                }
+
+                self.begin_scope();
+                if let Some(scope) = self.scopes.last_mut() {
+                    scope.insert(String::from("this"), true);
+                }
+                for method in methods {
+                    if let Stmt::Function(name, params, body) = method {
+                        let declaration = if name.lexeme == "init" {
+                            FunctionType::Initializer
+                        } else {
+                            FunctionType::Method
+                        };
+                        self.resolve_function(params, body, declaration);
+                    }
+                }
+                self.end_scope();
+
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+
+                self.current_class = enclosing_class;
+            }
+            Stmt::ExprStmt(expr) | Stmt::Print(expr) => self.resolve_expr(expr),
+            Stmt::Function(name, params, body) => {
+                self.declare(name);
+                self.define(name);
+                self.resolve_function(params, body, FunctionType::Function);
+            }
+            Stmt::Return(keyword, expr) => {
+                if let Some(expr) = expr {
+                    if self.current_function == FunctionType::Initializer {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+                    self.resolve_expr(expr);
+                }
+            }
+            Stmt::VarDeclaration(name, initializer) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Block(stmts) => {
+                self.begin_scope();
+                self.resolve_stmts(stmts);
+                self.end_scope();
+            }
+            Stmt::IfStmt(condition, then_branch, else_branch) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::While(condition, body) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+            }
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assignment(id, name, value) => {
+                self.resolve_expr(value);
+                self.resolve_local(*id, name);
+            }
+            Expr::Binary(left, _, right) | Expr::Logical(left, _, right) => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call(callee, _, args) => {
+                self.resolve_expr(callee);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            Expr::Get(object, _) => self.resolve_expr(object),
+            Expr::Grouping(expr) | Expr::Unary(_, expr) => self.resolve_expr(expr),
+            Expr::Set(object, _, value) => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::Super(id, keyword, _) => {
+                match self.current_class {
+                    ClassType::None => {
+                        self.error(keyword, "Can't use 'super' outside of a class.");
+                        return;
+                    }
+                    ClassType::Class => {
+                        self.error(keyword, "Can't use 'super' in a class with no superclass.");
+                        return;
+                    }
+                    ClassType::Subclass => {}
+                }
+                self.resolve_local(*id, keyword);
+            }
+            Expr::This(id, keyword) => {
+                if self.current_class == ClassType::None {
+                    self.error(keyword, "Can't use 'this' outside of a class.");
+                    return;
+                }
+                self.resolve_local(*id, keyword);
+            }
+            Expr::TrueLiteral(_)
+            | Expr::FalseLiteral(_)
+            | Expr::NilLiteral(_)
+            | Expr::NumberLiteral(..)
+            | Expr::StringLiteral(..) => {}
+            Expr::Variable(id, name) => {
+                if let Some(scope) = self.scopes.last() {
+                    if scope.get(&name.lexeme) == Some(&false) {
+                        self.error(name, "Can't read local variable in its own initializer.");
+                    }
+                }
+                self.resolve_local(*id, name);
+            }
+        }
+    }
+
+    // The function's parameters live in the scope created for the call, and
+    // the body is executed directly in that scope rather than in a nested block.
+    fn resolve_function(&mut self, params: &[Token], body: &[Stmt], declaration: FunctionType) {
+        let enclosing_function = self.current_function;
+        self.current_function = declaration;
+
+        self.begin_scope();
+        for param in params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve_stmts(body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn resolve_local(&mut self, id: ExprId, name: &Token) {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if scope.contains_key(&name.lexeme) {
+                self.locals.insert(id, depth);
+                return;
+            }
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &Token) {
+        let redeclared = match self.scopes.last_mut() {
+            Some(scope) => scope.insert(name.lexeme.clone(), false).is_some(),
+            None => false,
+        };
+        if redeclared {
+            self.error(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str) {
+        self.errors
+            .push(LoxError::at_token(ErrorKind::ResolveError, token, message));
+    }
+}
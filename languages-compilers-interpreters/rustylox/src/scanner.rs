@@ -1,4 +1,4 @@
-use super::error::{error, ErrorKind};
+use super::error::{ErrorKind, LoxError, Span};
 use super::token::{Literal, Token, TokenType};
 use std::collections::HashMap;
 use std::iter::Peekable;
@@ -10,13 +10,19 @@ pub struct Scanner<'a> {
     pub tokens: Vec<Token>,
     line: u32,
     column: u32,
+    // Where the token being scanned starts: its line, and the 1-based column
+    // of its first character.
+    start_line: u32,
+    start_column: u32,
     current: u32,
     start: u32,
     reserved: HashMap<&'a str, TokenType>,
     pub chars: Peekable<Chars<'a>>,
+    errors: Vec<LoxError>,
+    keep_comments: bool,
 }
 
-pub fn default_reserved<'a>() -> HashMap<&'static str, TokenType> {
+pub fn default_reserved() -> HashMap<&'static str, TokenType> {
     let mut reserved = HashMap::new();
     reserved.insert("break", TokenType::Break);
     reserved.insert("if", TokenType::If);
@@ -46,38 +52,62 @@ impl<'a> Scanner<'a> {
             tokens: Vec::new(),
             line: 1,
             column: 0,
+            start_line: 1,
+            start_column: 0,
             current: 0,
             start: 0,
-            reserved: reserved,
+            reserved,
             chars: source.chars().peekable(),
+            errors: Vec::new(),
+            keep_comments: false,
         }
     }
 
+    /// Emits comments as `Comment` tokens instead of discarding them, for
+    /// tools such as the formatter that need to reproduce them. The parser
+    /// doesn't accept these tokens, so filter them out before parsing.
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
     fn done(&self) -> bool {
-        return self.current >= (self.source.len() as u32);
+        self.current >= (self.source.len() as u32)
     }
 
     fn add_token(&mut self, token_type: TokenType, literal: Option<Literal>) {
         self.tokens.push(Token::new(
             token_type,
             self.substring().to_string(),
-            self.line,
-            self.column,
+            self.start_line,
+            self.start_column,
             literal,
         ));
     }
 
+    // `current` is a byte offset into the source, so it moves by the width
+    // of each character while `column` counts characters.
     fn advance(&mut self) -> Option<char> {
-        self.current += 1;
+        let c = self.chars.next();
+        self.current += c.map_or(0, |c| c.len_utf8() as u32);
         self.column += 1;
-        self.chars.next()
+        if c == Some('\n') {
+            self.line += 1;
+            self.column = 0;
+        }
+        c
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
     }
 
     fn match_char(&mut self, c: char) -> bool {
         if let Some(next) = self.chars.peek() {
             if *next == c {
                 self.chars.next();
-                self.current += 1;
+                self.current += c.len_utf8() as u32;
+                self.column += 1;
                 return true;
             }
         }
@@ -88,45 +118,101 @@ impl<'a> Scanner<'a> {
         &self.source[(self.start as usize)..(self.current as usize)]
     }
 
+    // Strings may span lines. The lexeme keeps its quotes and escapes as
+    // written; the literal value has the escapes decoded.
     fn string(&mut self) {
+        let mut value = String::new();
         loop {
-            while *self.chars.peek().or_else(|| Some(&'\0')).unwrap() != '"' && !self.done() {
-                if *self.chars.peek().unwrap() == '\n' {
-                    self.line += 1;
-                    self.column = 0;
+            match self.advance() {
+                Some('"') => break,
+                Some('\\') => {
+                    if let Some(c) = self.escape() {
+                        value.push(c);
+                    }
+                }
+                Some(c) => value.push(c),
+                None => {
+                    self.error(
+                        Span::new(self.start_line, self.start_column, self.start_column),
+                        "Unterminated string.",
+                    );
+                    return;
+                }
+            }
+        }
+
+        self.add_token(TokenType::String, Some(Literal::String(value)));
+    }
+
+    // Decodes the escape sequence following a backslash: `\n`, `\t`, `\"`,
+    // `\\` or `\u{XXXX}` with one to six hex digits.
+    fn escape(&mut self) -> Option<char> {
+        let line = self.line;
+        let start = self.column;
+        let decoded = match self.advance() {
+            Some('n') => Some('\n'),
+            Some('t') => Some('\t'),
+            Some('"') => Some('"'),
+            Some('\\') => Some('\\'),
+            Some('u') if self.match_char('{') => {
+                let mut digits = String::new();
+                while self.peek().is_some_and(|c| c.is_ascii_hexdigit()) {
+                    digits.push(self.advance().unwrap());
+                }
+                if (1..=6).contains(&digits.len()) && self.match_char('}') {
+                    u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                } else {
+                    None
                 }
-                self.advance();
             }
+            _ => None,
+        };
 
-            if self.done() {
-                error(
-                    self.line,
-                    self.start,
-                    self.current,
-                    String::from("Unterminated string"),
-                    ErrorKind::ScanError,
-                );
-                return;
+        if decoded.is_none() {
+            let end = if self.line == line {
+                self.column
+            } else {
+                start
+            };
+            self.error(Span::new(line, start, end), "Invalid escape sequence.");
+        }
+        decoded
+    }
+
+    // Block comments nest, so `/* a /* b */ c */` is a single comment.
+    fn block_comment(&mut self) {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.advance() {
+                Some('/') if self.match_char('*') => depth += 1,
+                Some('*') if self.match_char('/') => depth -= 1,
+                Some(_) => {}
+                None => {
+                    self.error(
+                        Span::new(self.start_line, self.start_column, self.start_column + 1),
+                        "Unterminated block comment.",
+                    );
+                    return;
+                }
             }
+        }
 
-            self.advance();
-            self.add_token(
-                TokenType::String,
-                Some(Literal::String(self.substring().to_string())),
-            );
-            break;
+        if self.keep_comments {
+            self.add_token(TokenType::Comment, None);
         }
     }
 
     fn number(&mut self) {
-        while self.chars.peek().unwrap_or(&'\0').is_digit(10) {
+        while self.chars.peek().unwrap_or(&'\0').is_ascii_digit() {
             self.advance();
         }
 
         if *self.chars.peek().unwrap_or(&'\0') == '.' {
             self.advance();
 
-            while self.chars.peek().unwrap_or(&'\0').is_digit(10) {
+            while self.chars.peek().unwrap_or(&'\0').is_ascii_digit() {
                 self.advance();
             }
         }
@@ -136,7 +222,7 @@ impl<'a> Scanner<'a> {
     }
 
     fn identifier(&mut self) {
-        while self.chars.peek().unwrap_or(&'❤').is_ascii_alphabetic()
+        while self.chars.peek().unwrap_or(&'❤').is_ascii_alphanumeric()
             || *self.chars.peek().unwrap_or(&'❤') == '_'
         {
             self.advance();
@@ -160,6 +246,8 @@ impl<'a> Scanner<'a> {
 
     fn scan_token(&mut self) {
         if let Some(c) = self.advance() {
+            self.start_line = self.line;
+            self.start_column = self.column;
             match c {
                 '(' => self.add_token(TokenType::LeftParen, None),
                 ')' => self.add_token(TokenType::RightParen, None),
@@ -217,33 +305,29 @@ impl<'a> Scanner<'a> {
                                 }
                             }
                         }
+                        if self.keep_comments {
+                            self.add_token(TokenType::Comment, None);
+                        }
+                    } else if self.match_char('*') {
+                        self.block_comment();
                     } else {
                         self.add_token(TokenType::Slash, None);
                     }
                 }
 
-                ' ' => self.column += 1,
-                '\t' => self.column += 4,
-                '\r' => {}
-
-                '\n' => {
-                    self.line += 1;
-                    self.column = 0;
-                }
+                // `advance` has already moved to the next line for '\n'.
+                ' ' | '\t' | '\r' | '\n' => {}
 
                 '"' => self.string(),
                 _ => {
-                    if c.is_digit(10) {
+                    if c.is_ascii_digit() {
                         self.number();
-                    } else if c.is_ascii_alphabetic() {
+                    } else if c.is_ascii_alphabetic() || c == '_' {
                         self.identifier();
                     } else {
-                        error(
-                            self.line,
-                            self.column,
-                            self.column + (self.current - self.start),
-                            String::from("Unknown character"),
-                            ErrorKind::ScanError,
+                        self.error(
+                            Span::new(self.line, self.start_column, self.column),
+                            "Unexpected character.",
                         );
                         self.add_token(TokenType::Unknown, None);
                     }
@@ -252,7 +336,14 @@ impl<'a> Scanner<'a> {
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, ErrorKind> {
+    fn error(&mut self, span: Span, message: &str) {
+        self.errors
+            .push(LoxError::new(ErrorKind::ScanError, span, message));
+    }
+
+    // Scanning continues past errors so that every problem in the source is
+    // reported at once.
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, Vec<LoxError>> {
         while !self.done() {
             self.start = self.current;
             self.scan_token();
@@ -261,9 +352,112 @@ impl<'a> Scanner<'a> {
             TokenType::EOF,
             String::from(""),
             self.line,
-            self.column,
+            self.column + 1,
             None,
         ));
+        if !self.errors.is_empty() {
+            return Err(self.errors.clone());
+        }
         Ok(self.tokens.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(source: &str) -> Result<Vec<Token>, Vec<LoxError>> {
+        Scanner::new(default_reserved(), source).scan_tokens()
+    }
+
+    fn string_value(source: &str) -> String {
+        match &scan(source).unwrap()[0].literal {
+            Some(Literal::String(s)) => s.clone(),
+            other => panic!("expected a string literal, got {:?}", other),
+        }
+    }
+
+    fn positions(tokens: &[Token]) -> Vec<(TokenType, u32, u32)> {
+        tokens
+            .iter()
+            .map(|t| (t.token_type.clone(), t.line, t.column))
+            .collect()
+    }
+
+    #[test]
+    fn decodes_escape_sequences() {
+        assert_eq!(
+            string_value(r#""a\nb\tc\"d\\e\u{48}\u{1F600}""#),
+            "a\nb\tc\"d\\eH\u{1F600}"
+        );
+    }
+
+    #[test]
+    fn reports_invalid_escape_sequences() {
+        for source in &[r#""\q""#, r#""\u{}""#, r#""\u{110000}""#, r#""\u48""#] {
+            let errors = scan(source).unwrap_err();
+            assert_eq!(errors.len(), 1, "{}", source);
+            assert_eq!(errors[0].message, "Invalid escape sequence.");
+            assert_eq!(errors[0].span.start, 2, "{}", source);
+        }
+    }
+
+    #[test]
+    fn tracks_positions_across_multi_line_strings() {
+        let tokens = scan("var s = \"one\ntwo\";\n  s;").unwrap();
+        assert_eq!(
+            positions(&tokens),
+            vec![
+                (TokenType::Var, 1, 1),
+                (TokenType::Identifier, 1, 5),
+                (TokenType::Equal, 1, 7),
+                (TokenType::String, 1, 9),
+                (TokenType::Semicolon, 2, 5),
+                (TokenType::Identifier, 3, 3),
+                (TokenType::Semicolon, 3, 4),
+                (TokenType::EOF, 3, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unterminated_strings_where_they_start() {
+        let errors = scan("print 1;\nprint \"oops\n\n").unwrap_err();
+        assert_eq!(errors[0].message, "Unterminated string.");
+        assert_eq!(errors[0].span, Span::new(2, 7, 7));
+    }
+
+    #[test]
+    fn skips_nested_block_comments() {
+        let tokens = scan("/* a /* b */\n c */ 1 /**/ 2").unwrap();
+        assert_eq!(
+            positions(&tokens),
+            vec![
+                (TokenType::Number, 2, 7),
+                (TokenType::Number, 2, 14),
+                (TokenType::EOF, 2, 15),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unterminated_block_comments() {
+        let errors = scan("1;\n /* a /* b */").unwrap_err();
+        assert_eq!(errors[0].message, "Unterminated block comment.");
+        assert_eq!(errors[0].span, Span::new(2, 2, 3));
+    }
+
+    #[test]
+    fn keeps_comments_as_trivia_when_asked() {
+        let tokens = Scanner::new(default_reserved(), "// line\n/* block\n */ 1")
+            .with_comments()
+            .scan_tokens()
+            .unwrap();
+        let comments: Vec<&str> = tokens
+            .iter()
+            .filter(|t| t.token_type == TokenType::Comment)
+            .map(|t| t.lexeme.as_str())
+            .collect();
+        assert_eq!(comments, vec!["// line", "/* block\n */"]);
+    }
+}
@@ -49,6 +49,9 @@ pub enum TokenType {
     While,
     Break,
 
+    // Trivia, only produced when the scanner is asked to keep comments.
+    Comment,
+
     EOF,
 
     Unknown,
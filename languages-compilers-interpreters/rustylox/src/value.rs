@@ -1,8 +1,14 @@
-use std::fmt::Display;
+use super::class::{Class, Instance};
+use super::function::{Function, NativeFunction};
+use std::fmt;
+use std::rc::Rc;
 
-#[derive(Clone, Debug, Display)]
+#[derive(Clone, Debug)]
 pub enum Value {
-    Break,
+    Function(Rc<Function>),
+    NativeFunction(Rc<NativeFunction>),
+    Class(Rc<Class>),
+    Instance(Rc<Instance>),
     Nil,
     True,
     False,
@@ -10,30 +16,77 @@ pub enum Value {
     String(String),
 }
 
-impl Into<Option<f64>> for Value {
-    fn into(self) -> Option<f64> {
-        match self {
+impl Value {
+    // Lox equality: values of different types are never equal, strings compare
+    // by content, and functions, classes and instances compare by identity.
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Nil, Value::Nil) => true,
+            (Value::True, Value::True) => true,
+            (Value::False, Value::False) => true,
+            (Value::Number(l), Value::Number(r)) => l == r,
+            (Value::String(l), Value::String(r)) => l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::NativeFunction(l), Value::NativeFunction(r)) => Rc::ptr_eq(l, r),
+            (Value::Class(l), Value::Class(r)) => Rc::ptr_eq(l, r),
+            (Value::Instance(l), Value::Instance(r)) => Rc::ptr_eq(l, r),
+            _ => false,
+        }
+    }
+}
+
+impl From<Value> for Option<f64> {
+    fn from(value: Value) -> Self {
+        match value {
             Value::Number(n) => Some(n),
             _ => None,
         }
     }
 }
 
-impl Into<Option<String>> for Value {
-    fn into(self) -> Option<String> {
-        match self {
+impl From<Value> for Option<String> {
+    fn from(value: Value) -> Self {
+        match value {
             Value::String(s) => Some(s),
             _ => None,
         }
     }
 }
 
-impl Into<Option<bool>> for Value {
-    fn into(self) -> Option<bool> {
-        match self {
+impl From<Value> for Option<bool> {
+    fn from(value: Value) -> Self {
+        match value {
             Value::True => Some(true),
             Value::False => Some(false),
             _ => None,
         }
     }
 }
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        if b {
+            Value::True
+        } else {
+            Value::False
+        }
+    }
+}
+
+// Values print the way a Lox program would write them: `nil`, `true`, and
+// numbers without a trailing `.0`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Function(function) => write!(f, "{}", function),
+            Value::NativeFunction(function) => write!(f, "{}", function),
+            Value::Class(class) => write!(f, "{}", class),
+            Value::Instance(instance) => write!(f, "{}", instance),
+            Value::Nil => write!(f, "nil"),
+            Value::True => write!(f, "true"),
+            Value::False => write!(f, "false"),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+        }
+    }
+}
@@ -0,0 +1,652 @@
+use super::chunk::{Chunk, OpCode};
+use super::value::{Function, Value};
+use crate::ast::{Expr, Stmt};
+use crate::error::{ErrorKind, LoxError, Span};
+use crate::token::{Token, TokenType};
+use std::rc::Rc;
+
+const MAX_LOCALS: usize = 256;
+const MAX_UPVALUES: usize = 256;
+
+/// Compiles a resolved program into the bytecode for its top-level script.
+/// The program must already have passed the `Resolver`, which reports the
+/// errors that would otherwise be found here, so the only errors left are
+/// the VM's own limits, such as the number of locals in a function.
+pub fn compile(stmts: &[Stmt]) -> Result<Rc<Function>, Vec<LoxError>> {
+    let mut compiler = Compiler {
+        states: vec![FunctionState::new("", FunctionKind::Script)],
+        span: Span::default(),
+        errors: Vec::new(),
+    };
+    for stmt in stmts {
+        compiler.stmt(stmt);
+    }
+    compiler.emit_return();
+
+    let state = compiler.states.pop().unwrap();
+    if !compiler.errors.is_empty() {
+        return Err(compiler.errors);
+    }
+    Ok(Rc::new(state.function))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionKind {
+    Script,
+    Function,
+    Method,
+    Initializer,
+}
+
+struct Local {
+    name: String,
+    depth: usize,
+    is_captured: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+struct UpvalueRef {
+    index: u8,
+    is_local: bool,
+}
+
+struct Loop {
+    scope_depth: usize,
+    // Jumps emitted for `break`, patched to land after the loop.
+    breaks: Vec<usize>,
+}
+
+// Everything tracked while compiling one function. Functions nest, so the
+// compiler keeps a stack of these.
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    upvalues: Vec<UpvalueRef>,
+    scope_depth: usize,
+    loops: Vec<Loop>,
+}
+
+impl FunctionState {
+    fn new(name: &str, kind: FunctionKind) -> Self {
+        // Slot 0 holds the function being called, or the receiver in methods.
+        let slot_zero = match kind {
+            FunctionKind::Method | FunctionKind::Initializer => "this",
+            _ => "",
+        };
+        FunctionState {
+            function: Function {
+                name: name.to_string(),
+                ..Function::default()
+            },
+            kind,
+            locals: vec![Local {
+                name: slot_zero.to_string(),
+                depth: 0,
+                is_captured: false,
+            }],
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            loops: Vec::new(),
+        }
+    }
+}
+
+struct Compiler {
+    states: Vec<FunctionState>,
+    // The source span given to the bytes emitted next.
+    span: Span,
+    errors: Vec<LoxError>,
+}
+
+impl Compiler {
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Break => self.break_stmt(),
+            Stmt::Class(name, superclass, methods) => self.class(name, superclass, methods),
+            Stmt::ExprStmt(expr) => self.expr_stmt(expr, OpCode::Pop),
+            Stmt::Function(name, params, body) => {
+                let global = self.declare_variable(name);
+                self.function(name, params, body, FunctionKind::Function);
+                self.define_variable(global);
+            }
+            Stmt::Print(expr) => self.expr_stmt(expr, OpCode::Print),
+            Stmt::Return(keyword, value) => {
+                self.at(keyword);
+                match value {
+                    Some(value) => {
+                        self.expr(value);
+                        self.emit_op(OpCode::Return);
+                    }
+                    None => self.emit_return(),
+                }
+            }
+            Stmt::VarDeclaration(name, initializer) => {
+                self.at(name);
+                match initializer {
+                    Some(initializer) => self.expr(initializer),
+                    None => self.emit_op(OpCode::Nil),
+                }
+                let global = self.declare_variable(name);
+                self.define_variable(global);
+            }
+            Stmt::Block(stmts) => {
+                self.begin_scope();
+                for stmt in stmts {
+                    self.stmt(stmt);
+                }
+                self.end_scope();
+            }
+            Stmt::IfStmt(condition, then_branch, else_branch) => {
+                self.start(condition);
+                self.expr(condition);
+                let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+                self.stmt(then_branch);
+                let else_jump = self.emit_jump(OpCode::Jump);
+
+                self.patch_jump(then_jump);
+                self.emit_op(OpCode::Pop);
+                if let Some(else_branch) = else_branch {
+                    self.stmt(else_branch);
+                }
+                self.patch_jump(else_jump);
+            }
+            Stmt::While(condition, body) => {
+                let loop_start = self.chunk().code.len();
+                self.start(condition);
+                self.expr(condition);
+                let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+                self.emit_op(OpCode::Pop);
+
+                let scope_depth = self.state().scope_depth;
+                self.state_mut().loops.push(Loop {
+                    scope_depth,
+                    breaks: Vec::new(),
+                });
+                self.stmt(body);
+                self.emit_loop(loop_start);
+
+                self.patch_jump(exit_jump);
+                self.emit_op(OpCode::Pop);
+                // `break` leaves after the condition has been popped.
+                let finished = self.state_mut().loops.pop().unwrap();
+                for jump in finished.breaks {
+                    self.patch_jump(jump);
+                }
+            }
+        }
+    }
+
+    // The statement's own instruction is placed where its expression starts,
+    // as `print` and `;` aren't kept in the AST.
+    fn expr_stmt(&mut self, expr: &Expr, op: OpCode) {
+        self.start(expr);
+        let span = self.span;
+        self.expr(expr);
+        self.span = span;
+        self.emit_op(op);
+    }
+
+    // Discards the locals declared inside the loop, then jumps past its end.
+    fn break_stmt(&mut self) {
+        let state = self.state();
+        let loop_depth = match state.loops.last() {
+            Some(innermost) => innermost.scope_depth,
+            None => return,
+        };
+        let exits: Vec<OpCode> = state
+            .locals
+            .iter()
+            .rev()
+            .take_while(|local| local.depth > loop_depth)
+            .map(|local| {
+                if local.is_captured {
+                    OpCode::CloseUpvalue
+                } else {
+                    OpCode::Pop
+                }
+            })
+            .collect();
+        for op in exits {
+            self.emit_op(op);
+        }
+
+        let jump = self.emit_jump(OpCode::Jump);
+        self.state_mut().loops.last_mut().unwrap().breaks.push(jump);
+    }
+
+    fn class(&mut self, name: &Token, superclass: &Option<Expr>, methods: &[Stmt]) {
+        self.at(name);
+        let name_constant = self.identifier_constant(&name.lexeme);
+        let global = self.declare_variable(name);
+        self.emit_op_u16(OpCode::Class, name_constant);
+        self.define_variable(global);
+
+        // Methods of a subclass capture `super` from a scope wrapped around
+        // the class body.
+        if let Some(superclass) = superclass {
+            self.expr(superclass);
+            self.begin_scope();
+            self.add_local("super");
+            self.at(name);
+            self.load(&name.lexeme);
+            if let Expr::Variable(_, superclass_name) = superclass {
+                self.at(superclass_name);
+            }
+            self.emit_op(OpCode::Inherit);
+        }
+
+        self.at(name);
+        self.load(&name.lexeme);
+        for method in methods {
+            if let Stmt::Function(method_name, params, body) = method {
+                let kind = if method_name.lexeme == "init" {
+                    FunctionKind::Initializer
+                } else {
+                    FunctionKind::Method
+                };
+                self.function(method_name, params, body, kind);
+                let constant = self.identifier_constant(&method_name.lexeme);
+                self.emit_op_u16(OpCode::Method, constant);
+            }
+        }
+        self.emit_op(OpCode::Pop);
+
+        if superclass.is_some() {
+            self.end_scope();
+        }
+    }
+
+    fn function(&mut self, name: &Token, params: &[Token], body: &[Stmt], kind: FunctionKind) {
+        self.states.push(FunctionState::new(&name.lexeme, kind));
+        self.begin_scope();
+        for param in params {
+            self.add_local(&param.lexeme);
+        }
+        self.state_mut().function.arity = params.len();
+        for stmt in body {
+            self.stmt(stmt);
+        }
+        self.emit_return();
+
+        // The new frame is thrown away on return, so there's no need to
+        // close the function's scope.
+        let mut state = self.states.pop().unwrap();
+        state.function.upvalue_count = state.upvalues.len();
+        let constant = self.make_constant(Value::Function(Rc::new(state.function)));
+
+        self.at(name);
+        self.emit_op_u16(OpCode::Closure, constant);
+        for upvalue in state.upvalues {
+            self.emit(upvalue.is_local as u8);
+            self.emit(upvalue.index);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Assignment(_, name, value) => {
+                self.expr(value);
+                self.at(name);
+                self.store(&name.lexeme);
+            }
+            Expr::Binary(left, op, right) => {
+                self.expr(left);
+                self.expr(right);
+                self.at(op);
+                match op.token_type {
+                    TokenType::Plus => self.emit_op(OpCode::Add),
+                    TokenType::Minus => self.emit_op(OpCode::Subtract),
+                    TokenType::Star => self.emit_op(OpCode::Multiply),
+                    TokenType::Slash => self.emit_op(OpCode::Divide),
+                    TokenType::EqualEqual => self.emit_op(OpCode::Equal),
+                    TokenType::BangEqual => self.emit_ops(OpCode::Equal, OpCode::Not),
+                    TokenType::Greater => self.emit_op(OpCode::Greater),
+                    TokenType::GreaterEqual => self.emit_op(OpCode::GreaterEqual),
+                    TokenType::Less => self.emit_op(OpCode::Less),
+                    TokenType::LessEqual => self.emit_op(OpCode::LessEqual),
+                    _ => self.error(&format!("Unknown binary operator '{}'.", op.lexeme)),
+                }
+            }
+            Expr::Call(callee, paren, args) => {
+                self.expr(callee);
+                for arg in args {
+                    self.expr(arg);
+                }
+                self.at(paren);
+                if args.len() > u8::MAX as usize {
+                    self.error("Can't have more than 255 arguments.");
+                }
+                self.emit_op_u8(OpCode::Call, args.len() as u8);
+            }
+            Expr::Get(object, name) => {
+                self.expr(object);
+                self.at(name);
+                let constant = self.identifier_constant(&name.lexeme);
+                self.emit_op_u16(OpCode::GetProperty, constant);
+            }
+            Expr::Grouping(expr) => self.expr(expr),
+            Expr::TrueLiteral(token) => {
+                self.at(token);
+                self.emit_op(OpCode::True);
+            }
+            Expr::FalseLiteral(token) => {
+                self.at(token);
+                self.emit_op(OpCode::False);
+            }
+            Expr::NilLiteral(token) => {
+                self.at(token);
+                self.emit_op(OpCode::Nil);
+            }
+            Expr::NumberLiteral(token, n) => {
+                self.at(token);
+                self.emit_constant(Value::Number(*n));
+            }
+            Expr::StringLiteral(token, s) => {
+                self.at(token);
+                self.emit_constant(Value::String(s.as_str().into()));
+            }
+            Expr::Set(object, name, value) => {
+                self.expr(object);
+                self.expr(value);
+                self.at(name);
+                let constant = self.identifier_constant(&name.lexeme);
+                self.emit_op_u16(OpCode::SetProperty, constant);
+            }
+            Expr::Super(_, keyword, method) => {
+                self.at(keyword);
+                self.load("this");
+                self.load("super");
+                self.at(method);
+                let constant = self.identifier_constant(&method.lexeme);
+                self.emit_op_u16(OpCode::GetSuper, constant);
+            }
+            Expr::This(_, keyword) => {
+                self.at(keyword);
+                self.load("this");
+            }
+            Expr::Unary(op, operand) => {
+                self.expr(operand);
+                self.at(op);
+                match op.token_type {
+                    TokenType::Minus => self.emit_op(OpCode::Negate),
+                    _ => self.emit_op(OpCode::Not),
+                }
+            }
+            Expr::Variable(_, name) => {
+                self.at(name);
+                self.load(&name.lexeme);
+            }
+            Expr::Logical(left, op, right) => {
+                self.expr(left);
+                self.at(op);
+                if op.token_type == TokenType::And {
+                    let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    self.emit_op(OpCode::Pop);
+                    self.expr(right);
+                    self.patch_jump(end_jump);
+                } else {
+                    let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+                    let end_jump = self.emit_jump(OpCode::Jump);
+                    self.patch_jump(else_jump);
+                    self.emit_op(OpCode::Pop);
+                    self.expr(right);
+                    self.patch_jump(end_jump);
+                }
+            }
+        }
+    }
+
+    // Variables.
+
+    fn declare_variable(&mut self, name: &Token) -> Option<u16> {
+        if self.state().scope_depth > 0 {
+            self.add_local(&name.lexeme);
+            None
+        } else {
+            Some(self.identifier_constant(&name.lexeme))
+        }
+    }
+
+    fn define_variable(&mut self, global: Option<u16>) {
+        if let Some(constant) = global {
+            self.emit_op_u16(OpCode::DefineGlobal, constant);
+        }
+    }
+
+    fn add_local(&mut self, name: &str) {
+        if self.state().locals.len() == MAX_LOCALS {
+            self.error("Too many local variables in function.");
+            return;
+        }
+        let depth = self.state().scope_depth;
+        self.state_mut().locals.push(Local {
+            name: name.to_string(),
+            depth,
+            is_captured: false,
+        });
+    }
+
+    fn load(&mut self, name: &str) {
+        let top = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(top, name) {
+            self.emit_op_u8(OpCode::GetLocal, slot);
+        } else if let Some(index) = self.resolve_upvalue(top, name) {
+            self.emit_op_u8(OpCode::GetUpvalue, index);
+        } else {
+            let constant = self.identifier_constant(name);
+            self.emit_op_u16(OpCode::GetGlobal, constant);
+        }
+    }
+
+    fn store(&mut self, name: &str) {
+        let top = self.states.len() - 1;
+        if let Some(slot) = self.resolve_local(top, name) {
+            self.emit_op_u8(OpCode::SetLocal, slot);
+        } else if let Some(index) = self.resolve_upvalue(top, name) {
+            self.emit_op_u8(OpCode::SetUpvalue, index);
+        } else {
+            let constant = self.identifier_constant(name);
+            self.emit_op_u16(OpCode::SetGlobal, constant);
+        }
+    }
+
+    fn resolve_local(&self, state: usize, name: &str) -> Option<u8> {
+        self.states[state]
+            .locals
+            .iter()
+            .rposition(|local| local.name == name)
+            .map(|slot| slot as u8)
+    }
+
+    // Finds `name` in an enclosing function, threading an upvalue through
+    // every function in between.
+    fn resolve_upvalue(&mut self, state: usize, name: &str) -> Option<u8> {
+        if state == 0 {
+            return None;
+        }
+
+        if let Some(slot) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[slot as usize].is_captured = true;
+            return Some(self.add_upvalue(state, slot, true));
+        }
+
+        let index = self.resolve_upvalue(state - 1, name)?;
+        Some(self.add_upvalue(state, index, false))
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: u8, is_local: bool) -> u8 {
+        let upvalue = UpvalueRef { index, is_local };
+        let upvalues = &self.states[state].upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing as u8;
+        }
+        if upvalues.len() == MAX_UPVALUES {
+            self.error("Too many closure variables in function.");
+            return 0;
+        }
+
+        self.states[state].upvalues.push(upvalue);
+        (self.states[state].upvalues.len() - 1) as u8
+    }
+
+    fn begin_scope(&mut self) {
+        self.state_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self) {
+        self.state_mut().scope_depth -= 1;
+        let depth = self.state().scope_depth;
+        while let Some(local) = self.state().locals.last() {
+            if local.depth <= depth {
+                break;
+            }
+            let op = if local.is_captured {
+                OpCode::CloseUpvalue
+            } else {
+                OpCode::Pop
+            };
+            self.emit_op(op);
+            self.state_mut().locals.pop();
+        }
+    }
+
+    // Emitting bytecode.
+
+    fn state(&self) -> &FunctionState {
+        self.states.last().unwrap()
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state_mut().function.chunk
+    }
+
+    fn at(&mut self, token: &Token) {
+        self.span = Span::from(token);
+    }
+
+    fn start(&mut self, expr: &Expr) {
+        self.at(first_token(expr));
+    }
+
+    fn emit(&mut self, byte: u8) {
+        let span = self.span;
+        self.chunk().write(byte, span);
+    }
+
+    fn emit_op(&mut self, op: OpCode) {
+        self.emit(op as u8);
+    }
+
+    fn emit_ops(&mut self, first: OpCode, second: OpCode) {
+        self.emit_op(first);
+        self.emit_op(second);
+    }
+
+    fn emit_op_u8(&mut self, op: OpCode, operand: u8) {
+        self.emit_op(op);
+        self.emit(operand);
+    }
+
+    fn emit_op_u16(&mut self, op: OpCode, operand: u16) {
+        self.emit_op(op);
+        for byte in operand.to_be_bytes().iter() {
+            self.emit(*byte);
+        }
+    }
+
+    fn emit_return(&mut self) {
+        if self.state().kind == FunctionKind::Initializer {
+            self.emit_op_u8(OpCode::GetLocal, 0);
+        } else {
+            self.emit_op(OpCode::Nil);
+        }
+        self.emit_op(OpCode::Return);
+    }
+
+    fn emit_constant(&mut self, value: Value) {
+        let constant = self.make_constant(value);
+        self.emit_op_u16(OpCode::Constant, constant);
+    }
+
+    fn make_constant(&mut self, value: Value) -> u16 {
+        let index = self.chunk().add_constant(value);
+        if index > u16::MAX as usize {
+            self.error("Too many constants in one chunk.");
+            return 0;
+        }
+        index as u16
+    }
+
+    // Names are looked up by string, so each is stored once per chunk.
+    fn identifier_constant(&mut self, name: &str) -> u16 {
+        let existing = self
+            .chunk()
+            .constants
+            .iter()
+            .position(|constant| matches!(constant, Value::String(s) if &**s == name));
+        match existing {
+            Some(index) if index <= u16::MAX as usize => index as u16,
+            _ => self.make_constant(Value::String(name.into())),
+        }
+    }
+
+    // Emits a jump with a placeholder offset and returns where the offset
+    // is, for `patch_jump`.
+    fn emit_jump(&mut self, op: OpCode) -> usize {
+        self.emit_op_u16(op, u16::MAX);
+        self.chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        let jump = self.chunk().code.len() - offset - 2;
+        if jump > u16::MAX as usize {
+            self.error("Too much code to jump over.");
+            return;
+        }
+        let bytes = (jump as u16).to_be_bytes();
+        self.chunk().code[offset..offset + 2].copy_from_slice(&bytes);
+    }
+
+    fn emit_loop(&mut self, loop_start: usize) {
+        // The offset also skips the Loop instruction's own three bytes.
+        let offset = self.chunk().code.len() - loop_start + 3;
+        if offset > u16::MAX as usize {
+            self.error("Loop body too large.");
+        }
+        self.emit_op_u16(OpCode::Loop, offset as u16);
+    }
+
+    fn error(&mut self, message: &str) {
+        self.errors
+            .push(LoxError::new(ErrorKind::CompileError, self.span, message));
+    }
+}
+
+// The leftmost token an expression keeps. A grouping doesn't keep its
+// parentheses, so it starts where its inner expression does.
+fn first_token(expr: &Expr) -> &Token {
+    match expr {
+        Expr::Assignment(_, name, _)
+        | Expr::Super(_, name, _)
+        | Expr::This(_, name)
+        | Expr::Unary(name, _)
+        | Expr::Variable(_, name)
+        | Expr::TrueLiteral(name)
+        | Expr::FalseLiteral(name)
+        | Expr::NilLiteral(name)
+        | Expr::NumberLiteral(name, _)
+        | Expr::StringLiteral(name, _) => name,
+        Expr::Binary(left, _, _)
+        | Expr::Logical(left, _, _)
+        | Expr::Call(left, _, _)
+        | Expr::Get(left, _)
+        | Expr::Set(left, _, _)
+        | Expr::Grouping(left) => first_token(left),
+    }
+}
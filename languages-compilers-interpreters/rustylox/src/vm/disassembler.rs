@@ -0,0 +1,195 @@
+use super::chunk::{Chunk, OpCode};
+use super::value::{Function, Value};
+
+/// Renders a function's bytecode one instruction per line, as
+/// `offset line OPCODE operand 'constant'`, followed by the bytecode of every
+/// function declared inside it. A line number is shown as `|` when it's the
+/// same as the previous instruction's.
+pub fn disassemble(function: &Function) -> String {
+    let mut out = String::new();
+    disassemble_function(function, &mut out);
+    out
+}
+
+fn disassemble_function(function: &Function, out: &mut String) {
+    let name = if function.name.is_empty() {
+        "<script>"
+    } else {
+        &function.name
+    };
+    *out += &format!("== {} ==\n", name);
+
+    let chunk = &function.chunk;
+    let mut offset = 0;
+    while offset < chunk.code.len() {
+        let (text, next) = disassemble_instruction(chunk, offset);
+        *out += &text;
+        out.push('\n');
+        offset = next;
+    }
+
+    for constant in &chunk.constants {
+        if let Value::Function(inner) = constant {
+            out.push('\n');
+            disassemble_function(inner, out);
+        }
+    }
+}
+
+/// Renders the instruction at `offset` and returns it along with the offset
+/// of the next instruction. A `Closure`'s upvalues take a line each.
+pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> (String, usize) {
+    let line = chunk.spans[offset].line;
+    let mut text = if offset > 0 && chunk.spans[offset - 1].line == line {
+        format!("{:04}    | ", offset)
+    } else {
+        format!("{:04} {:4} ", offset, line)
+    };
+
+    let op = match OpCode::from_byte(chunk.code[offset]) {
+        Some(op) => op,
+        None => {
+            text += &format!("Unknown opcode {}", chunk.code[offset]);
+            return (text, offset + 1);
+        }
+    };
+
+    let next = match op {
+        OpCode::Constant
+        | OpCode::GetGlobal
+        | OpCode::DefineGlobal
+        | OpCode::SetGlobal
+        | OpCode::GetProperty
+        | OpCode::SetProperty
+        | OpCode::GetSuper
+        | OpCode::Class
+        | OpCode::Method => {
+            let index = chunk.read_u16(offset + 1);
+            text += &format!(
+                "{:<16} {:4} '{}'",
+                op.name(),
+                index,
+                chunk.constants[index as usize]
+            );
+            offset + 3
+        }
+        OpCode::GetLocal
+        | OpCode::SetLocal
+        | OpCode::GetUpvalue
+        | OpCode::SetUpvalue
+        | OpCode::Call => {
+            text += &format!("{:<16} {:4}", op.name(), chunk.code[offset + 1]);
+            offset + 2
+        }
+        OpCode::Jump | OpCode::JumpIfFalse | OpCode::Loop => {
+            let jump = chunk.read_u16(offset + 1) as usize;
+            let target = if op == OpCode::Loop {
+                offset + 3 - jump
+            } else {
+                offset + 3 + jump
+            };
+            text += &format!("{:<16} {:4} -> {}", op.name(), offset, target);
+            offset + 3
+        }
+        OpCode::Closure => {
+            let index = chunk.read_u16(offset + 1);
+            let constant = &chunk.constants[index as usize];
+            text += &format!("{:<16} {:4} {}", op.name(), index, constant);
+
+            let mut next = offset + 3;
+            if let Value::Function(function) = constant {
+                for _ in 0..function.upvalue_count {
+                    let kind = if chunk.code[next] == 1 {
+                        "local"
+                    } else {
+                        "upvalue"
+                    };
+                    text += &format!(
+                        "\n{:04}      |                     {} {}",
+                        next,
+                        kind,
+                        chunk.code[next + 1]
+                    );
+                    next += 2;
+                }
+            }
+            next
+        }
+        _ => {
+            text += op.name();
+            offset + 1
+        }
+    };
+    (text, next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::{default_reserved, Scanner};
+    use crate::vm::compiler::compile;
+
+    fn disassemble_source(source: &str) -> String {
+        let tokens = Scanner::new(default_reserved(), source)
+            .scan_tokens()
+            .unwrap();
+        let stmts = Parser::new(tokens).parse().unwrap();
+        disassemble(&compile(&stmts).unwrap())
+    }
+
+    #[test]
+    fn shows_offsets_lines_operands_and_constants() {
+        assert_eq!(
+            disassemble_source("var a = 1.5;\nprint a +\n  \"b\";"),
+            "== <script> ==\n\
+             0000    1 OP_CONSTANT         0 '1.5'\n\
+             0003    | OP_DEFINE_GLOBAL    1 'a'\n\
+             0006    2 OP_GET_GLOBAL       1 'a'\n\
+             0009    3 OP_CONSTANT         2 'b'\n\
+             0012    2 OP_ADD\n\
+             0013    | OP_PRINT\n\
+             0014    | OP_NIL\n\
+             0015    | OP_RETURN\n"
+        );
+    }
+
+    #[test]
+    fn places_literals_on_their_own_lines() {
+        assert_eq!(
+            disassemble_source("print 1;\n\"a\" + 2;\nnil;\ntrue == false;"),
+            "== <script> ==\n\
+             0000    1 OP_CONSTANT         0 '1'\n\
+             0003    | OP_PRINT\n\
+             0004    2 OP_CONSTANT         1 'a'\n\
+             0007    | OP_CONSTANT         2 '2'\n\
+             0010    | OP_ADD\n\
+             0011    | OP_POP\n\
+             0012    3 OP_NIL\n\
+             0013    | OP_POP\n\
+             0014    4 OP_TRUE\n\
+             0015    | OP_FALSE\n\
+             0016    | OP_EQUAL\n\
+             0017    | OP_POP\n\
+             0018    | OP_NIL\n\
+             0019    | OP_RETURN\n"
+        );
+    }
+
+    #[test]
+    fn shows_jump_targets_and_nested_functions() {
+        let out = disassemble_source(
+            "fun outer() {\n  var x = 1;\n  fun inner() { return x; }\n  while (x) x = nil;\n}",
+        );
+        assert!(
+            out.contains("OP_CLOSURE          1 <fn inner>\n"),
+            "{}",
+            out
+        );
+        assert!(out.contains("|                     local 1\n"), "{}", out);
+        assert!(out.contains("OP_JUMP_IF_FALSE"), "{}", out);
+        assert!(out.contains("\n== outer ==\n"), "{}", out);
+        assert!(out.contains("\n== inner ==\n"), "{}", out);
+        assert!(out.contains("OP_GET_UPVALUE      0\n"), "{}", out);
+    }
+}
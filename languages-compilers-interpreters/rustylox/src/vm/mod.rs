@@ -0,0 +1,692 @@
+//! A bytecode backend: `compiler` turns the resolved AST into chunks of
+//! bytecode and `Vm` runs them on a value stack, following the design of
+//! clox from the second half of _Crafting Interpreters_.
+
+pub mod chunk;
+pub mod compiler;
+pub mod disassembler;
+pub mod value;
+
+use self::chunk::OpCode;
+use self::disassembler::disassemble_instruction;
+use self::value::{BoundMethod, Class, Closure, Function, Instance, Native, Upvalue, Value};
+use crate::ast::Stmt;
+use crate::error::{ErrorKind, LoxError, Span};
+use crate::function::{clock, NativeFn};
+use crate::output::Output;
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::{default_reserved, Scanner};
+use crate::MAX_CALL_DEPTH;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+
+struct CallFrame {
+    closure: Rc<Closure>,
+    ip: usize,
+    // Where the frame's locals start on the stack; slot 0 is the callee.
+    slots: usize,
+}
+
+/// Runs programs compiled to bytecode. Globals persist between calls to
+/// `interpret`, as they do between calls to the tree-walker's `evaluate`.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<CallFrame>,
+    // Keyed by the name constants themselves, so defining or assigning a
+    // global never allocates.
+    globals: HashMap<Rc<str>, Value>,
+    // Upvalues still pointing at stack slots, closed when the slot is popped.
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    output: Output,
+    // Where each instruction is logged before it runs, if anywhere.
+    trace: Option<Output>,
+    max_call_depth: usize,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut vm = Vm {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            output: Output::default(),
+            trace: None,
+            max_call_depth: MAX_CALL_DEPTH,
+        };
+        vm.define_native("clock", 0, clock);
+        vm
+    }
+
+    /// Sends the text of `print` statements somewhere other than stdout.
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    /// Before each instruction runs, writes the value stack and the
+    /// disassembled instruction to `writer`.
+    pub fn trace_execution<W: Write + 'static>(&mut self, writer: W) {
+        self.trace = Some(Output::writer(writer));
+    }
+
+    /// Sets how deeply function calls can nest before a program fails with
+    /// "Stack overflow.".
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.max_call_depth = depth;
+    }
+
+    /// Installs a Rust function as a global that scripts can call, the same
+    /// way as `Interpreter::define_native`. Only nil, booleans, numbers and
+    /// strings can be passed to it or returned from it. Errors returned
+    /// without a source location are reported at the call site.
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        let native = Native {
+            name: name.to_string(),
+            arity,
+            function,
+        };
+        self.globals
+            .insert(name.into(), Value::Native(Rc::new(native)));
+    }
+
+    /// Scans, parses, resolves, compiles and runs `source`. Like
+    /// `Lox::eval_str`, it stops at the first stage that reports errors.
+    pub fn eval_str(&mut self, source: &str) -> Result<(), Vec<LoxError>> {
+        let tokens = Scanner::new(default_reserved(), source).scan_tokens()?;
+        let stmts = Parser::new(tokens).parse()?;
+        Resolver::new().resolve(&stmts)?;
+        self.interpret(&stmts)
+    }
+
+    /// Compiles and runs a program that has already passed the `Resolver`.
+    pub fn interpret(&mut self, stmts: &[Stmt]) -> Result<(), Vec<LoxError>> {
+        let function = compiler::compile(stmts)?;
+        self.run_script(function).map_err(|err| vec![err])
+    }
+
+    fn run_script(&mut self, function: Rc<Function>) -> Result<(), LoxError> {
+        let closure = Rc::new(Closure {
+            function,
+            upvalues: Vec::new(),
+        });
+        self.stack.push(Value::Closure(Rc::clone(&closure)));
+        let result = self.call(closure, 0).and_then(|_| self.run());
+        if result.is_err() {
+            // Leave the VM ready for the next program.
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+        }
+        result
+    }
+
+    fn run(&mut self) -> Result<(), LoxError> {
+        loop {
+            if self.trace.is_some() {
+                self.trace_instruction()?;
+            }
+
+            let byte = self.read_byte();
+            let op = match OpCode::from_byte(byte) {
+                Some(op) => op,
+                None => return Err(self.error(format!("Unknown opcode {}.", byte))),
+            };
+
+            match op {
+                OpCode::Constant => {
+                    let constant = self.read_constant();
+                    self.stack.push(constant);
+                }
+                OpCode::Nil => self.stack.push(Value::Nil),
+                OpCode::True => self.stack.push(Value::Bool(true)),
+                OpCode::False => self.stack.push(Value::Bool(false)),
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::GetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack.push(self.stack[slot].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0).clone();
+                }
+                OpCode::GetGlobal => {
+                    let name = self.read_string();
+                    match self.globals.get(&*name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(self.error(format!("Undefined variable '{}'.", name))),
+                    }
+                }
+                OpCode::DefineGlobal => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                OpCode::SetGlobal => {
+                    let name = self.read_string();
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(&name) {
+                        Some(global) => *global = value,
+                        None => return Err(self.error(format!("Undefined variable '{}'.", name))),
+                    }
+                }
+                OpCode::GetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                    let value = match &*upvalue.borrow() {
+                        Upvalue::Open(slot) => self.stack[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                OpCode::SetUpvalue => {
+                    let index = self.read_byte() as usize;
+                    let upvalue = Rc::clone(&self.frame().closure.upvalues[index]);
+                    let value = self.peek(0).clone();
+                    match &mut *upvalue.borrow_mut() {
+                        Upvalue::Open(slot) => self.stack[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
+                    };
+                }
+                OpCode::GetProperty => {
+                    let name = self.read_string();
+                    let instance = match self.peek(0) {
+                        Value::Instance(instance) => Rc::clone(instance),
+                        _ => return Err(self.error("Only instances have properties.")),
+                    };
+                    let field = instance.fields.borrow().get(&*name).cloned();
+                    let value = match field {
+                        Some(value) => value,
+                        None => self.bind_method(
+                            &instance.class,
+                            &name,
+                            Value::Instance(instance.clone()),
+                        )?,
+                    };
+                    self.pop();
+                    self.stack.push(value);
+                }
+                OpCode::SetProperty => {
+                    let name = self.read_string();
+                    let value = self.pop();
+                    match self.pop() {
+                        Value::Instance(instance) => {
+                            instance
+                                .fields
+                                .borrow_mut()
+                                .insert(name.to_string(), value.clone());
+                            self.stack.push(value);
+                        }
+                        _ => return Err(self.error("Only instances have fields.")),
+                    }
+                }
+                OpCode::GetSuper => {
+                    let name = self.read_string();
+                    let superclass = match self.pop() {
+                        Value::Class(class) => class,
+                        _ => return Err(self.error("Invalid use of 'super'.")),
+                    };
+                    let receiver = self.pop();
+                    let method = self.bind_method(&superclass, &name, receiver)?;
+                    self.stack.push(method);
+                }
+                OpCode::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Bool(left.equals(&right)));
+                }
+                OpCode::Greater => self.binary_op(|l, r| Value::Bool(l > r))?,
+                OpCode::GreaterEqual => self.binary_op(|l, r| Value::Bool(l >= r))?,
+                OpCode::Less => self.binary_op(|l, r| Value::Bool(l < r))?,
+                OpCode::LessEqual => self.binary_op(|l, r| Value::Bool(l <= r))?,
+                OpCode::Add => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let sum = match (&left, &right) {
+                        (Value::Number(l), Value::Number(r)) => Value::Number(l + r),
+                        (Value::String(l), Value::String(r)) => {
+                            Value::String(format!("{}{}", l, r).into())
+                        }
+                        _ => return Err(self.error("Operands must be two numbers or two strings.")),
+                    };
+                    self.stack.push(sum);
+                }
+                OpCode::Subtract => self.binary_op(|l, r| Value::Number(l - r))?,
+                OpCode::Multiply => self.binary_op(|l, r| Value::Number(l * r))?,
+                OpCode::Divide => self.binary_op(|l, r| Value::Number(l / r))?,
+                OpCode::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(value.is_falsey()));
+                }
+                OpCode::Negate => match self.pop() {
+                    Value::Number(n) => self.stack.push(Value::Number(-n)),
+                    _ => return Err(self.error("Operand must be a number.")),
+                },
+                OpCode::Print => {
+                    let value = self.pop();
+                    self.output.print(&value.to_string())?;
+                }
+                OpCode::Jump => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip += offset;
+                }
+                OpCode::JumpIfFalse => {
+                    let offset = self.read_u16() as usize;
+                    if self.peek(0).is_falsey() {
+                        self.frame_mut().ip += offset;
+                    }
+                }
+                OpCode::Loop => {
+                    let offset = self.read_u16() as usize;
+                    self.frame_mut().ip -= offset;
+                }
+                OpCode::Call => {
+                    let arg_count = self.read_byte() as usize;
+                    let callee = self.peek(arg_count).clone();
+                    self.call_value(callee, arg_count)?;
+                }
+                OpCode::Closure => {
+                    let function = match self.read_constant() {
+                        Value::Function(function) => function,
+                        _ => return Err(self.error("Closure operand must be a function.")),
+                    };
+                    let mut upvalues = Vec::with_capacity(function.upvalue_count);
+                    for _ in 0..function.upvalue_count {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+                        let upvalue = if is_local {
+                            let slot = self.frame().slots + index;
+                            self.capture_upvalue(slot)
+                        } else {
+                            Rc::clone(&self.frame().closure.upvalues[index])
+                        };
+                        upvalues.push(upvalue);
+                    }
+                    let closure = Closure { function, upvalues };
+                    self.stack.push(Value::Closure(Rc::new(closure)));
+                }
+                OpCode::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                OpCode::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+                    self.stack.truncate(frame.slots);
+                    if self.frames.is_empty() {
+                        return Ok(());
+                    }
+                    self.stack.push(result);
+                }
+                OpCode::Class => {
+                    let name = self.read_string();
+                    let class = Class::new(&name);
+                    self.stack.push(Value::Class(Rc::new(class)));
+                }
+                OpCode::Inherit => {
+                    let superclass = match self.peek(1) {
+                        Value::Class(class) => Rc::clone(class),
+                        _ => return Err(self.error("Superclass must be a class.")),
+                    };
+                    if let Value::Class(subclass) = self.peek(0) {
+                        let methods = superclass.methods.borrow().clone();
+                        subclass.methods.borrow_mut().extend(methods);
+                    }
+                    self.pop();
+                }
+                OpCode::Method => {
+                    let name = self.read_string();
+                    if let (Value::Closure(method), Value::Class(class)) =
+                        (self.peek(0), self.peek(1))
+                    {
+                        class
+                            .methods
+                            .borrow_mut()
+                            .insert(name.to_string(), Rc::clone(method));
+                    }
+                    self.pop();
+                }
+            }
+        }
+    }
+
+    fn trace_instruction(&mut self) -> Result<(), LoxError> {
+        let frame = self.frames.last().unwrap();
+        let stack: String = self
+            .stack
+            .iter()
+            .map(|value| format!("[ {:?} ]", value))
+            .collect();
+        let (instruction, _) = disassemble_instruction(&frame.closure.function.chunk, frame.ip);
+
+        let trace = self.trace.as_mut().unwrap();
+        trace.print(&format!("          {}\n{}", stack, instruction))
+    }
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), LoxError> {
+        let callee_slot = self.stack.len() - arg_count - 1;
+        match callee {
+            Value::Closure(closure) => self.call(closure, arg_count),
+            Value::Native(native) => {
+                self.check_arity(native.arity, arg_count)?;
+                let args: Option<Vec<crate::Value>> = self.stack[callee_slot + 1..]
+                    .iter()
+                    .map(Option::from)
+                    .collect();
+                let args = args.ok_or_else(|| {
+                    self.error("Native functions only take nil, booleans, numbers and strings.")
+                })?;
+                let result = (native.function)(&args).map_err(|mut err| {
+                    if err.span.line == 0 {
+                        err.span = self.span();
+                    }
+                    err
+                })?;
+                let result = Option::from(result).ok_or_else(|| {
+                    self.error(
+                        "Native functions can only return nil, booleans, numbers and strings.",
+                    )
+                })?;
+                self.stack.truncate(callee_slot);
+                self.stack.push(result);
+                Ok(())
+            }
+            Value::Class(class) => {
+                let instance = Instance::new(Rc::clone(&class));
+                self.stack[callee_slot] = Value::Instance(Rc::new(instance));
+                let initializer = class.methods.borrow().get("init").cloned();
+                match initializer {
+                    Some(initializer) => self.call(initializer, arg_count),
+                    None => self.check_arity(0, arg_count),
+                }
+            }
+            Value::BoundMethod(bound) => {
+                self.stack[callee_slot] = bound.receiver.clone();
+                self.call(Rc::clone(&bound.method), arg_count)
+            }
+            _ => Err(self.error("Can only call functions and classes.")),
+        }
+    }
+
+    fn call(&mut self, closure: Rc<Closure>, arg_count: usize) -> Result<(), LoxError> {
+        self.check_arity(closure.function.arity, arg_count)?;
+        // The script's own frame isn't a call.
+        if self.frames.len() > self.max_call_depth {
+            return Err(self.error("Stack overflow."));
+        }
+        self.frames.push(CallFrame {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - arg_count - 1,
+        });
+        Ok(())
+    }
+
+    fn check_arity(&self, arity: usize, arg_count: usize) -> Result<(), LoxError> {
+        if arity == arg_count {
+            Ok(())
+        } else {
+            Err(self.error(format!(
+                "Expected {} arguments but got {}.",
+                arity, arg_count
+            )))
+        }
+    }
+
+    fn bind_method(&self, class: &Class, name: &str, receiver: Value) -> Result<Value, LoxError> {
+        match class.methods.borrow().get(name) {
+            Some(method) => Ok(Value::BoundMethod(Rc::new(BoundMethod {
+                receiver,
+                method: Rc::clone(method),
+            }))),
+            None => Err(self.error(format!("Undefined property '{}'.", name))),
+        }
+    }
+
+    // Reuses the open upvalue for `slot` if a closure already captured it,
+    // so that every closure sees the same variable.
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return Rc::clone(upvalue);
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(Rc::clone(&upvalue));
+        upvalue
+    }
+
+    // Moves the values of every stack slot from `first` up into the upvalues
+    // that refer to them.
+    fn close_upvalues(&mut self, first: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) if slot >= first => slot,
+                _ => return true,
+            };
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+            false
+        });
+    }
+
+    fn binary_op(&mut self, op: fn(f64, f64) -> Value) -> Result<(), LoxError> {
+        match (self.peek(1), self.peek(0)) {
+            (Value::Number(l), Value::Number(r)) => {
+                let result = op(*l, *r);
+                self.pop();
+                self.pop();
+                self.stack.push(result);
+                Ok(())
+            }
+            _ => Err(self.error("Operands must be numbers.")),
+        }
+    }
+
+    fn frame(&self) -> &CallFrame {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().unwrap()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let frame = self.frame_mut();
+        let byte = frame.closure.function.chunk.code[frame.ip];
+        frame.ip += 1;
+        byte
+    }
+
+    fn read_u16(&mut self) -> u16 {
+        let frame = self.frame_mut();
+        let value = frame.closure.function.chunk.read_u16(frame.ip);
+        frame.ip += 2;
+        value
+    }
+
+    fn read_constant(&mut self) -> Value {
+        let index = self.read_u16() as usize;
+        self.frame().closure.function.chunk.constants[index].clone()
+    }
+
+    fn read_string(&mut self) -> Rc<str> {
+        match self.read_constant() {
+            Value::String(s) => s,
+            other => panic!("expected a name constant, found {:?}", other),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("value stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    // Every byte of an instruction shares its span, so the last byte read
+    // locates the instruction being executed.
+    fn span(&self) -> Span {
+        self.frames
+            .last()
+            .map(|frame| frame.closure.function.chunk.spans[frame.ip.saturating_sub(1)])
+            .unwrap_or_default()
+    }
+
+    fn error(&self, message: impl Into<String>) -> LoxError {
+        LoxError::new(ErrorKind::RuntimeError, self.span(), message)
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Vm::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Result<Vec<String>, Vec<LoxError>> {
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let lines = Rc::clone(&printed);
+        let mut vm = Vm::new();
+        vm.set_output(Output::callback(move |line| {
+            lines.borrow_mut().push(line.to_string())
+        }));
+        vm.eval_str(source)?;
+        let printed = printed.borrow().clone();
+        Ok(printed)
+    }
+
+    #[test]
+    fn closures_share_captured_variables() {
+        let source = "
+            fun counter() {
+              var n = 0;
+              fun get() { return n; }
+              fun inc() { n = n + 1; }
+              inc(); inc();
+              return get;
+            }
+            print counter()();";
+        assert_eq!(run(source).unwrap(), vec!["2"]);
+    }
+
+    #[test]
+    fn break_pops_the_loop_body_locals() {
+        let source = "
+            var fs = nil;
+            for (var i = 0; i < 10; i = i + 1) {
+              var j = i * 2;
+              fun f() { return j; }
+              fs = f;
+              if (i == 3) break;
+            }
+            print fs();
+            var after = \"ok\";
+            print after;";
+        assert_eq!(run(source).unwrap(), vec!["6", "ok"]);
+    }
+
+    #[test]
+    fn runtime_errors_point_at_the_failing_operator() {
+        let errors = run("var a = 1;\nprint a +\n  \"b\";").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "Operands must be two numbers or two strings."
+        );
+        assert_eq!(errors[0].span, Span::new(2, 9, 9));
+    }
+
+    #[test]
+    fn deep_recursion_overflows_the_stack() {
+        let errors = run("fun f() { f(); } f();").unwrap_err();
+        assert_eq!(errors[0].message, "Stack overflow.");
+    }
+
+    #[test]
+    fn calls_nest_as_deeply_as_in_the_tree_walker() {
+        let source = "var depth = 0; fun f() { depth = depth + 1; f(); } f();";
+        for limit in [MAX_CALL_DEPTH, 10] {
+            let mut lox = crate::Lox::new();
+            lox.interpreter().set_max_call_depth(limit);
+            let errors = lox.eval_str(source).unwrap_err();
+            assert_eq!(errors[0].message, "Stack overflow.");
+            let depth = lox.eval_str("depth;").unwrap();
+            assert!(depth.equals(&crate::Value::Number(limit as f64)));
+
+            let printed = Rc::new(RefCell::new(Vec::new()));
+            let lines = Rc::clone(&printed);
+            let mut vm = Vm::new();
+            vm.set_output(Output::callback(move |line| {
+                lines.borrow_mut().push(line.to_string())
+            }));
+            vm.set_max_call_depth(limit);
+            let errors = vm.eval_str(source).unwrap_err();
+            assert_eq!(errors[0].message, "Stack overflow.");
+            vm.eval_str("print depth;").unwrap();
+            assert_eq!(*printed.borrow(), vec![limit.to_string()]);
+        }
+    }
+
+    #[test]
+    fn natives_run_on_both_backends() {
+        fn double(args: &[crate::Value]) -> Result<crate::Value, LoxError> {
+            match args[0] {
+                crate::Value::Number(n) => Ok(crate::Value::Number(n * 2.0)),
+                _ => Err(LoxError::new(
+                    ErrorKind::RuntimeError,
+                    Span::default(),
+                    "double() takes a number.",
+                )),
+            }
+        }
+
+        let mut lox = crate::Lox::new();
+        lox.interpreter().define_native("double", 1, double);
+        let value = lox.eval_str("double(21);").unwrap();
+        assert!(value.equals(&crate::Value::Number(42.0)));
+
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let lines = Rc::clone(&printed);
+        let mut vm = Vm::new();
+        vm.set_output(Output::callback(move |line| {
+            lines.borrow_mut().push(line.to_string())
+        }));
+        vm.define_native("double", 1, double);
+        vm.eval_str("print double(21);").unwrap();
+        assert_eq!(*printed.borrow(), vec!["42"]);
+
+        let errors = vm.eval_str("\n  double(nil);").unwrap_err();
+        assert_eq!(errors[0].message, "double() takes a number.");
+        assert_eq!(errors[0].span.line, 2);
+
+        let errors = vm.eval_str("fun f() {} double(f);").unwrap_err();
+        assert_eq!(
+            errors[0].message,
+            "Native functions only take nil, booleans, numbers and strings."
+        );
+    }
+
+    #[test]
+    fn globals_persist_between_programs() {
+        let mut vm = Vm::new();
+        vm.set_output(Output::writer(std::io::sink()));
+        vm.eval_str("var a = 1;").unwrap();
+        assert!(vm.eval_str("a = a + 1; print a;").is_ok());
+        assert!(vm.eval_str("print b;").is_err());
+        // A runtime error doesn't leave the VM in a broken state.
+        assert!(vm.eval_str("print a;").is_ok());
+    }
+}
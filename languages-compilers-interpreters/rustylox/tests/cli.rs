@@ -0,0 +1,235 @@
+//! Drives the `rustylox` binary the way a shell script or CI job would.
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("rustylox-cli-{}.lox", name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn run_file(path: &PathBuf) -> Output {
+    run_file_with(path, &[])
+}
+
+fn run_file_with(path: &PathBuf, flags: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rustylox"))
+        .args(flags)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn prints_only_program_output_by_default() {
+    let output = run_file(&script("quiet", "print \"hi\"; 1 + 2;"));
+    assert_eq!(stdout(&output), "hi\n");
+}
+
+#[test]
+fn dump_flags_print_tokens_ast_and_result() {
+    let path = script("dump", "1 + 2;");
+
+    let tokens = stdout(&run_file_with(&path, &["--dump-tokens"]));
+    assert!(tokens.starts_with("Tokens:\n"), "{}", tokens);
+    assert!(!tokens.contains("Parsed:"), "{}", tokens);
+
+    let ast = stdout(&run_file_with(&path, &["--dump-ast"]));
+    assert!(ast.starts_with("Parsed:\n"), "{}", ast);
+
+    let result = stdout(&run_file_with(&path, &["--dump-result"]));
+    assert_eq!(result, "Result:\n3\n");
+}
+
+#[test]
+fn exits_zero_for_a_successful_script() {
+    let output = run_file(&script("ok", "print 1 + 2;"));
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn exits_65_for_a_compile_error() {
+    let output = run_file(&script("parse-error", "print 1 +;"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn exits_65_for_a_resolve_error() {
+    let output = run_file(&script("resolve-error", "{ var a = 1; var a = 2; }"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn exits_70_for_a_runtime_error() {
+    let output = run_file(&script("runtime-error", "print -\"a\";"));
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn exits_74_for_an_unreadable_file() {
+    let output = run_file(&std::env::temp_dir().join("rustylox-cli-missing.lox"));
+    assert_eq!(output.status.code(), Some(74));
+}
+
+#[test]
+fn vm_backend_runs_a_file() {
+    let path = script(
+        "vm",
+        "fun adder(n) { fun add(x) { return x + n; } return add; }\nprint adder(1)(2);",
+    );
+    let output = run_file_with(&path, &["--backend=vm"]);
+    assert_eq!(stdout(&output), "3\n");
+    assert_eq!(output.status.code(), Some(0));
+
+    let output = run_file_with(&script("vm-error", "print -\"a\";"), &["--backend=vm"]);
+    assert_eq!(output.status.code(), Some(70));
+}
+
+#[test]
+fn disasm_prints_each_chunk() {
+    let path = script("disasm", "fun f() {\n  return 1;\n}\nprint f();");
+    let output = Command::new(env!("CARGO_BIN_EXE_rustylox"))
+        .arg("disasm")
+        .arg(&path)
+        .output()
+        .unwrap();
+    let out = stdout(&output);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        out.starts_with("== <script> ==\n0000    1 OP_CLOSURE          1 <fn f>\n"),
+        "{}",
+        out
+    );
+    assert!(
+        out.contains("== f ==\n0000    2 OP_CONSTANT         0 '1'\n"),
+        "{}",
+        out
+    );
+}
+
+#[test]
+fn trace_execution_prints_the_stack_before_each_instruction() {
+    let path = script("trace", "print 1 + 2;");
+    let output = run_file_with(&path, &["--trace-execution"]);
+    let trace = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        trace.contains("          [ <script> ][ 1 ][ 2 ]\n0006    | OP_ADD\n"),
+        "{}",
+        trace
+    );
+    assert!(
+        trace.contains("          [ <script> ][ 3 ]\n0007    | OP_PRINT\n"),
+        "{}",
+        trace
+    );
+    // The trace doesn't mix with what the program prints.
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn trace_execution_is_rejected_with_the_tree_walker() {
+    let path = script("trace-tree", "print 1;");
+    let output = run_file_with(&path, &["--backend=tree", "--trace-execution"]);
+    assert_ne!(output.status.code(), Some(0));
+    assert!(stdout(&output).is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--backend=vm"));
+}
+
+fn fmt(flags: &[&str], path: &PathBuf) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rustylox"))
+        .arg("fmt")
+        .args(flags)
+        .arg(path)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn fmt_rewrites_a_file_in_place() {
+    let path = script("fmt", "var a=1;\nif(a){print a;}");
+    assert_eq!(fmt(&[], &path).status.code(), Some(0));
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "var a = 1;\nif (a) {\n  print a;\n}\n"
+    );
+}
+
+#[test]
+fn fmt_check_fails_for_unformatted_files_without_changing_them() {
+    let path = script("fmt-check", "print 1+2;");
+    assert_eq!(fmt(&["--check"], &path).status.code(), Some(1));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "print 1+2;");
+
+    let path = script("fmt-check-ok", "print 1 + 2;\n");
+    assert_eq!(fmt(&["--check"], &path).status.code(), Some(0));
+}
+
+#[test]
+fn fmt_refuses_files_with_syntax_errors() {
+    let path = script("fmt-error", "print 1+;");
+    assert_eq!(fmt(&[], &path).status.code(), Some(65));
+    assert_eq!(fs::read_to_string(&path).unwrap(), "print 1+;");
+}
+
+fn repl(input: &str) -> Output {
+    repl_with(&[], input)
+}
+
+fn repl_with(flags: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rustylox"))
+        .arg("repl")
+        .args(flags)
+        .env("HOME", std::env::temp_dir())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn repl_runs_statements_spanning_several_lines() {
+    let output =
+        repl("var a = 1;\nwhile (a < 3) {\n  print a;\n\n  a = a + 1;\n}\nprint \"done\";\n");
+    assert_eq!(stdout(&output), "1\n2\ndone\n");
+}
+
+#[test]
+fn repl_prints_the_value_of_bare_expressions() {
+    let output = repl("var a = 2;\na * 3\n\"str\"\n");
+    assert_eq!(stdout(&output), "6\nstr\n");
+}
+
+#[test]
+fn repl_meta_commands() {
+    let output = repl(":ast -1 * (2)\nvar a = 1;\n:env\n:reset\n:env\n:nope\n");
+    assert_eq!(
+        stdout(&output),
+        "(* (- 1) (group 2))\n\
+         a = 1\nclock = <native fn clock>\n\
+         All definitions cleared.\n\
+         clock = <native fn clock>\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown command ':nope'"));
+}
+
+#[test]
+fn repl_dump_flags_apply_to_bare_expressions() {
+    let out = stdout(&repl_with(&["--dump-tokens", "--dump-ast"], "1 + 2\n"));
+    assert!(out.starts_with("Tokens:\n["), "{}", out);
+    assert!(out.contains("Parsed:\n(; (+ 1 2))\n\n3\n"), "{}", out);
+}
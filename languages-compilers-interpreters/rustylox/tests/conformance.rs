@@ -0,0 +1,283 @@
+//! Runs every `.lox` file under `tests/lox/` and checks what it printed,
+//! what it reported and how it exited against the expectations written in
+//! its comments, following the conventions of the Crafting Interpreters test
+//! suite:
+//!
+//! ```text
+//! print 1 + 2;          // expect: 3
+//! print -"a";           // expect runtime error: Operand must be a number.
+//! var a = ;             // [line 3] Error at ';': Expect expression.
+//! var b = ;             // Error at ';': Expect expression.
+//! ```
+//!
+//! The last form is shorthand for an error on the comment's own line. Each
+//! file is run by both the tree-walking interpreter and the bytecode VM.
+//!
+//! Each file is also run through the `rustylox` binary, whose stdout, stderr
+//! and exit status must match the library's run of the same file, with its
+//! errors rendered the way the binary reports them.
+
+use rustylox::{ErrorKind, Lox, LoxError, Output, Renderer, Vm};
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::rc::Rc;
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+
+#[derive(Debug, Default, PartialEq)]
+struct Outcome {
+    stdout: Vec<String>,
+    stderr: Vec<String>,
+    exit_code: i32,
+}
+
+fn expected_outcome(source: &str) -> Outcome {
+    let mut expected = Outcome::default();
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        if let Some(pos) = line.find(EXPECT) {
+            expected.stdout.push(line[pos + EXPECT.len()..].to_string());
+        } else if let Some(pos) = line.find(EXPECT_RUNTIME_ERROR) {
+            expected
+                .stderr
+                .push(line[pos + EXPECT_RUNTIME_ERROR.len()..].to_string());
+            expected.stderr.push(format!("[line {}]", line_number));
+            expected.exit_code = 70;
+        } else if let Some(pos) = line.find("// [line ") {
+            expected.stderr.push(line[pos + 3..].to_string());
+            expected.exit_code = 65;
+        } else if let Some(pos) = line.find("// Error") {
+            expected
+                .stderr
+                .push(format!("[line {}] {}", line_number, &line[pos + 3..]));
+            expected.exit_code = 65;
+        }
+    }
+    expected
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Backend {
+    TreeWalker,
+    Vm,
+}
+
+impl Backend {
+    fn flag(self) -> &'static str {
+        match self {
+            Backend::TreeWalker => "--backend=tree",
+            Backend::Vm => "--backend=vm",
+        }
+    }
+}
+
+/// A `.lox` file from `tests/lox/`.
+struct TestFile {
+    path: PathBuf,
+    source: String,
+}
+
+// Runs the file in-process, returning the lines it printed and the errors
+// that stopped it.
+fn run(backend: Backend, file: &TestFile) -> (Vec<String>, Vec<LoxError>) {
+    let printed = Rc::new(RefCell::new(Vec::new()));
+    let lines = Rc::clone(&printed);
+    let output = Output::callback(move |text| {
+        // A printed string can itself contain newlines.
+        let mut lines = lines.borrow_mut();
+        lines.extend(text.split('\n').map(String::from));
+    });
+
+    let result = match backend {
+        Backend::TreeWalker => {
+            let mut lox = Lox::new();
+            lox.interpreter().set_output(output);
+            lox.eval_str(&file.source).map(|_| ())
+        }
+        Backend::Vm => {
+            let mut vm = Vm::new();
+            vm.set_output(output);
+            vm.eval_str(&file.source)
+        }
+    };
+    let errors = result.err().unwrap_or_default();
+    (printed.take(), errors)
+}
+
+fn exit_code(errors: &[LoxError]) -> i32 {
+    errors.first().map_or(0, |err| err.kind.exit_code())
+}
+
+// What the file did when run in-process, with its errors in the reference
+// format.
+fn library_outcome(backend: Backend, file: &TestFile) -> Outcome {
+    let (stdout, errors) = run(backend, file);
+    Outcome {
+        stdout,
+        stderr: errors
+            .iter()
+            .flat_map(|err| reference_error(&file.source, err))
+            .collect(),
+        exit_code: exit_code(&errors),
+    }
+}
+
+// What the binary should do with the file: print the same lines as the
+// library and report its errors through the same renderer.
+fn rendered_outcome(backend: Backend, file: &TestFile) -> Outcome {
+    let (stdout, errors) = run(backend, file);
+    let file_name = file.path.display().to_string();
+    let rendered = Renderer::new(&file_name, &file.source).render_all(&errors);
+    Outcome {
+        stdout,
+        stderr: rendered.lines().map(String::from).collect(),
+        exit_code: exit_code(&errors),
+    }
+}
+
+fn binary_outcome(backend: Backend, file: &TestFile) -> Outcome {
+    let output = Command::new(env!("CARGO_BIN_EXE_rustylox"))
+        .arg(backend.flag())
+        .arg(&file.path)
+        .output()
+        .unwrap();
+    let lines = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .lines()
+            .map(String::from)
+            .collect()
+    };
+    Outcome {
+        stdout: lines(&output.stdout),
+        stderr: lines(&output.stderr),
+        exit_code: output.status.code().unwrap_or(-1),
+    }
+}
+
+// Formats an error the way the reference implementation does: a runtime
+// error's message followed by `[line N]`, and anything else as
+// `[line N] Error at 'lexeme': message`.
+fn reference_error(source: &str, err: &LoxError) -> Vec<String> {
+    if err.kind == ErrorKind::RuntimeError {
+        return vec![err.message.clone(), format!("[line {}]", err.span.line)];
+    }
+    let location = if err.kind == ErrorKind::ScanError {
+        String::new()
+    } else if err.notes.iter().any(|note| note == "at end of input") {
+        String::from(" at end")
+    } else {
+        let line = source.lines().nth(err.span.line as usize - 1).unwrap_or("");
+        let lexeme: String = line
+            .chars()
+            .skip(err.span.start as usize - 1)
+            .take((err.span.end - err.span.start + 1) as usize)
+            .collect();
+        format!(" at '{}'", lexeme)
+    };
+    vec![format!(
+        "[line {}] Error{}: {}",
+        err.span.line, location, err.message
+    )]
+}
+
+fn lox_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            lox_files(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "lox") {
+            files.push(path);
+        }
+    }
+}
+
+fn diff(label: &str, expected: &[String], actual: &[String]) -> String {
+    if expected == actual {
+        return String::new();
+    }
+    let mut out = format!("  {}:\n", label);
+    for line in expected {
+        out += &format!("    - {}\n", line);
+    }
+    for line in actual {
+        out += &format!("    + {}\n", line);
+    }
+    out
+}
+
+// Recursing to `MAX_CALL_DEPTH` in a debug build of the tree-walker comes
+// close to the native stack a test thread gets by default.
+const STACK_SIZE: usize = 512 * 1024 * 1024;
+
+#[test]
+fn lox_test_suite() {
+    run_test_suite(|backend, file| {
+        (
+            expected_outcome(&file.source),
+            library_outcome(backend, file),
+        )
+    });
+}
+
+#[test]
+fn lox_test_suite_through_the_binary() {
+    run_test_suite(|backend, file| {
+        (
+            rendered_outcome(backend, file),
+            binary_outcome(backend, file),
+        )
+    });
+}
+
+// Runs every file on both backends, comparing the expected and actual
+// outcomes that `outcomes` returns for each.
+fn run_test_suite(outcomes: fn(Backend, &TestFile) -> (Outcome, Outcome)) {
+    std::thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || check_files(outcomes))
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+fn check_files(outcomes: fn(Backend, &TestFile) -> (Outcome, Outcome)) {
+    let mut paths = Vec::new();
+    lox_files(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/lox"),
+        &mut paths,
+    );
+    paths.sort();
+    assert!(!paths.is_empty(), "no .lox files found under tests/lox");
+
+    let mut failures = Vec::new();
+    for path in paths.iter().cloned() {
+        let source = fs::read_to_string(&path).unwrap();
+        let file = TestFile { path, source };
+        for backend in [Backend::TreeWalker, Backend::Vm] {
+            let (expected, actual) = outcomes(backend, &file);
+            if expected != actual {
+                let mut report = format!("{} ({:?})\n", file.path.display(), backend);
+                report += &diff("stdout", &expected.stdout, &actual.stdout);
+                report += &diff("stderr", &expected.stderr, &actual.stderr);
+                if expected.exit_code != actual.exit_code {
+                    report += &format!(
+                        "  exit code: expected {}, got {}\n",
+                        expected.exit_code, actual.exit_code
+                    );
+                }
+                failures.push(report);
+            }
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} Lox test runs failed:\n\n{}",
+        failures.len(),
+        paths.len() * 2,
+        failures.join("\n")
+    );
+}